@@ -0,0 +1,267 @@
+use crate::camera::Camera;
+use crate::canvas;
+use crate::canvas::Canvas;
+use crate::math::Float;
+use crate::matrix::Matrix4x4;
+use crate::render;
+use crate::render::RenderSettings;
+use crate::tonemap::ToneMap;
+use crate::tonemap::ToneMapping;
+use crate::tuple::Point;
+use crate::tuple::Vector;
+use crate::world::World;
+
+/*
+    how a value moves from one keyframe to the next, the choice
+    is made on the keyframe it leaves from
+*/
+pub enum Interpolation {
+    Linear,
+    // eases out of the keyframe and into the next
+    Smooth,
+    // holds the value until the next keyframe
+    Step
+}
+
+pub trait Interpolate {
+    // the value a fraction t of the way from self to other
+    fn interpolate(&self, other: &Self, t: Float) -> Self;
+}
+
+impl Interpolate for crate::tuple::Tuple {
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        self.add(&other.subtract(self).multiplyf(t))
+    }
+}
+
+impl Interpolate for Matrix4x4 {
+    // through translation, rotation and scale, see Matrix4x4::decompose
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        self.decompose().interpolate(&other.decompose(), t).to_matrix()
+    }
+}
+
+pub struct Keyframe<T> {
+    pub time: Float,
+    pub value: T,
+    pub interpolation: Interpolation
+}
+
+/*
+    keyframes of one animated value, kept in time order,
+    before the first and after the last the value holds still
+*/
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>
+}
+
+impl<T: Interpolate + Clone> Track<T> {
+    pub fn new() -> Track<T> {
+        Track {
+            keyframes: Vec::new()
+        }
+    }
+
+    pub fn add(&mut self, time: Float, value: T, interpolation: Interpolation) {
+        let index = self.keyframes.iter().position(|k| k.time > time).unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, Keyframe { time, value, interpolation });
+    }
+
+    pub fn value_at(&self, time: Float) -> Option<T> {
+        let next = self.keyframes.iter().position(|k| k.time > time);
+        match next {
+            None => self.keyframes.last().map(|k| k.value.clone()),
+            Some(0) => Some(self.keyframes[0].value.clone()),
+            Some(index) => {
+                let from = &self.keyframes[index - 1];
+                let to = &self.keyframes[index];
+                let t = (time - from.time) / (to.time - from.time);
+                let t = match from.interpolation {
+                    Interpolation::Linear => t,
+                    Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
+                    Interpolation::Step => 0.0
+                };
+                Some(from.value.interpolate(&to.value, t))
+            }
+        }
+    }
+}
+
+impl<T: Interpolate + Clone> Default for Track<T> {
+    fn default() -> Track<T> {
+        Track::new()
+    }
+}
+
+// where the camera is and what it looks at, as for Matrix4x4::view_transform
+#[derive(Clone)]
+pub struct View {
+    pub from: Point,
+    pub to: Point,
+    pub up: Vector
+}
+
+impl Interpolate for View {
+    fn interpolate(&self, other: &Self, t: Float) -> Self {
+        View {
+            from: self.from.interpolate(&other.from, t),
+            to: self.to.interpolate(&other.to, t),
+            up: self.up.interpolate(&other.up, t)
+        }
+    }
+}
+
+/*
+    what changes over time in a scene, objects and lights are
+    picked by their index in World::objects and World::lights,
+    anything without a track is left as it is
+
+    times are in seconds and frame n is at n / frames_per_second
+*/
+pub struct Animation {
+    pub frames_per_second: Float,
+    pub camera: Track<View>,
+    pub object_transforms: Vec<(usize, Track<Matrix4x4>)>,
+    pub light_positions: Vec<(usize, Track<Point>)>
+}
+
+impl Animation {
+    pub fn new(frames_per_second: Float) -> Animation {
+        Animation {
+            frames_per_second,
+            camera: Track::new(),
+            object_transforms: Vec::new(),
+            light_positions: Vec::new()
+        }
+    }
+
+    pub fn frame_time(&self, frame: u32) -> Float {
+        frame as Float / self.frames_per_second
+    }
+
+    /*
+        poses the camera and world as they are at time
+
+        when the camera's shutter is open the animated objects also
+        get an end transform one frame later, so ray times from 0 to 1
+        span one frame, see Shape::set_end_transform
+    */
+    pub fn apply(&self, time: Float, camera: &mut Camera, world: &mut World) {
+        if let Some(view) = self.camera.value_at(time) {
+            camera.transform = Matrix4x4::view_transform(&view.from, &view.to, &view.up);
+        }
+        let motion_blur = camera.shutter_close > camera.shutter_open;
+        let next_time = time + 1.0 / self.frames_per_second;
+        for (index, track) in self.object_transforms.iter() {
+            if let (Some(object), Some(transform)) = (world.objects.get_mut(*index), track.value_at(time)) {
+                object.transform = transform;
+                object.set_end_transform(if motion_blur { track.value_at(next_time) } else { None });
+            }
+        }
+        for (index, track) in self.light_positions.iter() {
+            if let (Some(light), Some(position)) = (world.lights.get_mut(*index), track.value_at(time)) {
+                light.position = position;
+            }
+        }
+    }
+
+    /*
+        renders frames first to last inclusive into directory
+        as frame_0001.ppm, frame_0002.ppm and so on, clamped and
+        sRGB encoded for viewing
+    */
+    pub fn render_frames(
+        &self,
+        camera: &mut Camera,
+        world: &mut World,
+        settings: &RenderSettings,
+        first: u32,
+        last: u32,
+        directory: &std::path::Path) -> std::io::Result<()> {
+
+        let mapping = ToneMapping::new(ToneMap::Clamp);
+        for frame in first..=last {
+            self.apply(self.frame_time(frame), camera, world);
+            let mut canvas = Canvas::new(camera.hsize, camera.vsize);
+            render::render(camera, world, settings, &mut canvas);
+            std::fs::write(directory.join(frame_file_name(frame)), canvas::to_ppm(&mapping.apply(&canvas)))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn frame_file_name(frame: u32) -> String {
+    format!("frame_{:04}.ppm", frame)
+}
+
+#[test]
+fn track_test() {
+    use crate::point;
+
+    let mut track = Track::new();
+    assert!(track.value_at(1.0).is_none());
+    // added out of order on purpose
+    track.add(2.0, point!(10,0,0), Interpolation::Linear);
+    track.add(0.0, point!(0,0,0), Interpolation::Linear);
+    track.add(4.0, point!(10,10,0), Interpolation::Linear);
+
+    assert_eq!(track.value_at(-1.0), Some(point!(0,0,0)));
+    assert_eq!(track.value_at(0.5), Some(point!(2.5,0,0)));
+    assert_eq!(track.value_at(2.0), Some(point!(10,0,0)));
+    assert_eq!(track.value_at(3.0), Some(point!(10,5,0)));
+    assert_eq!(track.value_at(9.0), Some(point!(10,10,0)));
+
+    // smooth eases in and out but meets linear halfway
+    let mut track = Track::new();
+    track.add(0.0, point!(0,0,0), Interpolation::Smooth);
+    track.add(1.0, point!(1,0,0), Interpolation::Smooth);
+    assert_eq!(track.value_at(0.5), Some(point!(0.5,0,0)));
+    assert_eq!(track.value_at(0.25), Some(point!(0.15625,0,0)));
+
+    let mut track = Track::new();
+    track.add(0.0, point!(0,0,0), Interpolation::Step);
+    track.add(1.0, point!(1,0,0), Interpolation::Linear);
+    assert_eq!(track.value_at(0.9), Some(point!(0,0,0)));
+    assert_eq!(track.value_at(1.0), Some(point!(1,0,0)));
+}
+
+#[test]
+fn animation_apply_test() {
+    use crate::shape::Shape;
+    use crate::light::Light;
+    use crate::point;
+    use crate::vector;
+    use crate::rgb;
+
+    let mut world = World::new();
+    world.objects.push(Shape::sphere());
+    world.lights.push(Light::point_light(point!(0,10,0), rgb!(1,1,1)));
+    let mut camera = Camera::new(10, 10, 1.0);
+
+    let mut animation = Animation::new(10.0);
+    animation.camera.add(0.0, View { from: point!(0,0,-5), to: point!(0,0,0), up: vector!(0,1,0) }, Interpolation::Linear);
+    animation.camera.add(1.0, View { from: point!(0,0,-15), to: point!(0,0,0), up: vector!(0,1,0) }, Interpolation::Linear);
+    let mut spin = Track::new();
+    spin.add(0.0, crate::matrix::MATRIX_4X4_IDENTITY, Interpolation::Linear);
+    spin.add(1.0, Matrix4x4::rotation_y(crate::math::consts::PI / 2.0), Interpolation::Linear);
+    animation.object_transforms.push((0, spin));
+    let mut lift = Track::new();
+    lift.add(0.0, point!(0,10,0), Interpolation::Linear);
+    lift.add(1.0, point!(0,20,0), Interpolation::Linear);
+    animation.light_positions.push((0, lift));
+
+    // frame 5 is halfway
+    animation.apply(animation.frame_time(5), &mut camera, &mut world);
+    assert_eq!(camera.transform, Matrix4x4::view_transform(&point!(0,0,-10), &point!(0,0,0), &vector!(0,1,0)));
+    assert_eq!(world.objects[0].transform, Matrix4x4::rotation_y(crate::math::consts::PI / 4.0));
+    assert!(world.objects[0].end_transform().is_none());
+    assert_eq!(world.lights[0].position, point!(0,15,0));
+
+    // with the shutter open objects move over the next frame
+    camera.shutter_close = 0.5;
+    animation.apply(animation.frame_time(5), &mut camera, &mut world);
+    assert_eq!(world.objects[0].end_transform(), Some(&Matrix4x4::rotation_y(crate::math::consts::PI * 0.3)));
+
+    assert_eq!(frame_file_name(1), "frame_0001.ppm");
+    assert_eq!(frame_file_name(12345), "frame_12345.ppm");
+}
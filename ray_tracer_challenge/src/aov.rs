@@ -0,0 +1,154 @@
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::math::Float;
+use crate::ray::Ray;
+use crate::render;
+use crate::render::Region;
+use crate::render::RenderSettings;
+use crate::world;
+use crate::world::World;
+use crate::rgb;
+
+/*
+    arbitrary output variables, auxiliary images rendered alongside
+    the final color for compositing and debugging
+
+    they are taken from the ray through each pixel center and are not
+    averaged, an object id blended across an edge would mean nothing
+
+    depth     hit distance t along the camera ray, 0 where nothing is hit
+    normal    world space shading normal, x, y, z in red, green, blue,
+              so it runs from -1 to 1 and is 0 where nothing is hit
+    albedo    the surface color before lighting, black where nothing is hit
+    object_id the index in World::objects in every channel, -1 where
+              nothing is hit
+*/
+pub struct Aovs {
+    pub depth: Canvas,
+    pub normal: Canvas,
+    pub albedo: Canvas,
+    pub object_id: Canvas
+}
+
+impl Aovs {
+    pub fn new(width: u32, height: u32) -> Aovs {
+        Aovs {
+            depth: Canvas::new(width, height),
+            normal: Canvas::new(width, height),
+            albedo: Canvas::new(width, height),
+            object_id: Canvas::new(width, height)
+        }
+    }
+
+    /*
+        the same buffers remapped into 0 to 1 so the 8 bit formats can
+        show them, depth divided by the farthest hit, normals moved from
+        -1..1 to 0..1 and object ids spread over grey levels, anything
+        not hit stays black
+    */
+    pub fn viewable(&self) -> Aovs {
+        let largest = |canvas: &Canvas| canvas.rows().flatten().fold(0.0 as Float, |largest, color| largest.max(color.red));
+        let far = largest(&self.depth);
+        let ids = largest(&self.object_id) + 1.0;
+        Aovs {
+            depth: self.depth.map(|depth| if far > 0.0 { depth.multiplyf(1.0 / far) } else { depth.clone() }),
+            normal: self.normal.map(|normal| {
+                if *normal == Color::BLACK { Color::BLACK } else { normal.multiplyf(0.5).add(&rgb!(0.5,0.5,0.5)) }
+            }),
+            albedo: self.albedo.clone(),
+            object_id: self.object_id.map(|id| id.add(&Color::WHITE).multiplyf(1.0 / ids))
+        }
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, world: &World, ray: &Ray) {
+        let intersections = world::intersect(world, ray);
+        match world::surface_hit(&intersections) {
+            None => {
+                self.object_id.set_pixel(x, y, &rgb!(-1,-1,-1));
+            },
+            Some(intersection) => {
+                let comps = world::prepare_computations(ray, intersection);
+                let normal = &comps.normalv;
+                let id = object_index(world, comps.object) as Float;
+                self.depth.set_pixel(x, y, &rgb!(comps.t, comps.t, comps.t));
+                self.normal.set_pixel(x, y, &rgb!(normal.x, normal.y, normal.z));
+                self.albedo.set_pixel(x, y, &comps.object.material.color_at_time(comps.object, &comps.point, comps.time));
+                self.object_id.set_pixel(x, y, &rgb!(id, id, id));
+            }
+        }
+    }
+}
+
+fn object_index(world: &World, object: &crate::shape::Shape) -> usize {
+    world.objects.iter()
+        .position(|o| std::ptr::eq(o, object))
+        .expect("hit object is not in the world")
+}
+
+/*
+    render::render into canvas and return the aovs of the same view
+*/
+pub fn render_with_aovs(camera: &Camera, world: &World, settings: &RenderSettings, canvas: &mut Canvas) -> Aovs {
+    let mut aovs = Aovs::new(camera.hsize, camera.vsize);
+    render::render_tiles(&Region::full(camera), |x, y| {
+        let color = render::render_pixel(camera, world, settings, x, y);
+        canvas.set_pixel(x, y, &color);
+        aovs.set_pixel(x, y, world, &camera.ray_for_pixel(x, y));
+    });
+    aovs
+}
+
+#[test]
+fn render_with_aovs_test() {
+    use crate::shape::Shape;
+    use crate::light::Light;
+    use crate::point;
+    use crate::vector;
+    use crate::color::Color;
+
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-10,10,-10), rgb!(1,1,1)));
+    world.objects.push(Shape::plane());
+    let mut sphere = Shape::sphere();
+    sphere.transform = crate::matrix::Matrix4x4::translation(0.0, 1.0, 0.0);
+    sphere.material.color = rgb!(0.8,1,0.6);
+    world.objects.push(sphere);
+    let mut camera = Camera::new(11, 11, crate::math::consts::PI / 2.0);
+    camera.transform = crate::matrix::Matrix4x4::view_transform(&point!(0,1,-5), &point!(0,1,0), &vector!(0,1,0));
+
+    let mut canvas = Canvas::new(11, 11);
+    let aovs = render_with_aovs(&camera, &world, &RenderSettings::new(), &mut canvas);
+
+    // the center pixel looks straight at the front of the sphere
+    assert_eq!(canvas.get_pixel(5, 5), world.color_at(&camera.ray_for_pixel(5, 5)));
+    assert_eq!(aovs.depth.get_pixel(5, 5), rgb!(4,4,4));
+    assert_eq!(aovs.normal.get_pixel(5, 5), rgb!(0,0,-1));
+    assert_eq!(aovs.albedo.get_pixel(5, 5), rgb!(0.8,1,0.6));
+    assert_eq!(aovs.object_id.get_pixel(5, 5), rgb!(1,1,1));
+
+    // the bottom corner sees the floor
+    assert_eq!(aovs.object_id.get_pixel(0, 10), Color::BLACK);
+    assert_eq!(aovs.normal.get_pixel(0, 10), rgb!(0,1,0));
+
+    // the top corner sees nothing
+    assert_eq!(aovs.depth.get_pixel(0, 0), Color::BLACK);
+    assert_eq!(aovs.normal.get_pixel(0, 0), Color::BLACK);
+    assert_eq!(aovs.albedo.get_pixel(0, 0), Color::BLACK);
+    assert_eq!(aovs.object_id.get_pixel(0, 0), rgb!(-1,-1,-1));
+
+    // remapped into 0 to 1 for the 8 bit formats, the floor is farther
+    // than the sphere and the miss stays black
+    let viewable = aovs.viewable();
+    let far = aovs.depth.rows().flatten().map(|depth| depth.red).fold(0.0, crate::math::Float::max);
+    assert_eq!(viewable.depth.get_pixel(5, 5), rgb!(4,4,4).multiplyf(1.0 / far));
+    assert_eq!(viewable.normal.get_pixel(5, 5), rgb!(0.5,0.5,0));
+    assert_eq!(viewable.normal.get_pixel(0, 10), rgb!(0.5,1,0.5));
+    assert_eq!(viewable.albedo.get_pixel(5, 5), rgb!(0.8,1,0.6));
+    assert_eq!(viewable.object_id.get_pixel(5, 5), rgb!(1,1,1));
+    assert_eq!(viewable.object_id.get_pixel(0, 10), rgb!(0.5,0.5,0.5));
+    for canvas in &[&viewable.depth, &viewable.normal, &viewable.object_id] {
+        assert_eq!(canvas.get_pixel(0, 0), Color::BLACK);
+        assert!(canvas.rows().flatten().all(|color| color.red >= 0.0 && color.red <= 1.0));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::texture::Texture;
+use crate::texture::TextureFilter;
+use crate::texture::UvMapping;
+use crate::tuple::Vector;
+use crate::point;
+
+/*
+    what a ray sees when it leaves the scene without hitting anything
+*/
+pub enum Background {
+    Solid(Color),
+    // blends from bottom straight down to top straight up
+    Gradient { bottom: Color, top: Color },
+    // an image looked up by ray direction
+    Environment(Texture)
+}
+
+impl Background {
+    /*
+        a latitude / longitude panorama, 2:1 wide, with -z in the
+        middle of the left edge and straight up along the top row
+    */
+    pub fn equirectangular(canvas: Canvas) -> Background {
+        Background::Environment(Texture::new(canvas, UvMapping::Spherical, TextureFilter::Bilinear))
+    }
+
+    // six faces laid out as a horizontal cross, see texture::UvMapping::Cube
+    pub fn cube_map(canvas: Canvas) -> Background {
+        Background::Environment(Texture::new(canvas, UvMapping::Cube, TextureFilter::Bilinear))
+    }
+
+    pub fn color_at(&self, direction: &Vector) -> Color {
+        match self {
+            Background::Solid(color) => color.clone(),
+            Background::Gradient { bottom, top } => {
+                let fraction = (direction.normalize().y + 1.0) / 2.0;
+                bottom.add(&top.subtract(bottom).multiplyf(fraction))
+            },
+            Background::Environment(texture) => {
+                // the mappings take points, so treat the direction as a point on a unit sphere
+                let d = direction.normalize();
+                texture.color_at(&point!(d.x, d.y, d.z))
+            }
+        }
+    }
+}
+
+#[test]
+fn solid_background_test() {
+    use crate::vector;
+    use crate::rgb;
+
+    let background = Background::Solid(rgb!(0.2,0.3,0.4));
+    assert_eq!(background.color_at(&vector!(0,0,1)), rgb!(0.2,0.3,0.4));
+    assert_eq!(background.color_at(&vector!(1,-1,0)), rgb!(0.2,0.3,0.4));
+}
+
+#[test]
+fn gradient_background_test() {
+    use crate::vector;
+    use crate::rgb;
+
+    let background = Background::Gradient { bottom: Color::WHITE, top: rgb!(0,0,1) };
+    assert_eq!(background.color_at(&vector!(0,-1,0)), Color::WHITE);
+    assert_eq!(background.color_at(&vector!(0,5,0)), rgb!(0,0,1));
+    assert_eq!(background.color_at(&vector!(1,0,0)), rgb!(0.5,0.5,1));
+}
+
+#[test]
+fn environment_background_test() {
+    use crate::vector;
+    use crate::rgb;
+
+    // 4 x 2 panorama, top row red and bottom row blue
+    let mut canvas = Canvas::new(4, 2);
+    for x in 0..4 {
+        canvas.set_pixel(x, 0, &rgb!(1,0,0));
+        canvas.set_pixel(x, 1, &rgb!(0,0,1));
+    }
+    let background = Background::equirectangular(canvas);
+    assert_eq!(background.color_at(&vector!(0,1,0)), rgb!(1,0,0));
+    assert_eq!(background.color_at(&vector!(0,-3,0)), rgb!(0,0,1));
+    assert_eq!(background.color_at(&vector!(0,0,1)), rgb!(0.5,0,0.5));
+
+    // cube map picks the face the direction points at
+    let mut canvas = Canvas::new(4, 3);
+    canvas.set_pixel(1, 1, &Color::WHITE);
+    let background = Background::cube_map(canvas);
+    assert_eq!(background.color_at(&vector!(0,0,1)), Color::WHITE);
+    assert_eq!(background.color_at(&vector!(0,0,-1)), Color::BLACK);
+}
@@ -2,23 +2,27 @@ use crate::matrix::Matrix4x4;
 use crate::ray::Ray;
 use crate::point;
 use crate::vector;
+use crate::math::Float;
 
 pub struct Camera {
     pub hsize: u32,
     pub vsize: u32,
-    //field_of_view: f32,
+    //field_of_view: Float,
     pub transform: Matrix4x4,
-    pixel_size: f32,
-    half_width: f32,
-    half_height: f32
+    // rays are spread over the times the shutter is open, for motion blur
+    pub shutter_open: Float,
+    pub shutter_close: Float,
+    pixel_size: Float,
+    half_width: Float,
+    half_height: Float
 }
 
 impl Camera {
-    pub fn new(hsize: u32, vsize: u32, field_of_view: f32) -> Camera {
+    pub fn new(hsize: u32, vsize: u32, field_of_view: Float) -> Camera {
         let half_view = (field_of_view/2.0).tan();
-        let aspect = (hsize as f32) / (vsize as f32);
-        let half_width: f32;
-        let half_height: f32;
+        let aspect = (hsize as Float) / (vsize as Float);
+        let half_width: Float;
+        let half_height: Float;
         if aspect >= 1.0 {
             half_width = half_view;
             half_height = half_view / aspect;
@@ -32,16 +36,26 @@ impl Camera {
             vsize,
             //field_of_view,
             transform: crate::matrix::MATRIX_4X4_IDENTITY,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
             half_width,
             half_height,
-            pixel_size: half_width * 2.0 / (hsize as f32)
+            pixel_size: half_width * 2.0 / (hsize as Float)
         }
     }
 
     pub fn ray_for_pixel(&self, x: u32, y: u32) -> Ray {
-        // offset from the edge of the canvas to the pixel center
-        let xoffset = (x as f32 + 0.5) * self.pixel_size;
-        let yoffset = (y as f32 + 0.5) * self.pixel_size;
+        self.ray_for_pixel_offset(x, y, 0.5, 0.5)
+    }
+
+    /*
+        ray through a point inside the pixel, dx and dy are 0 to 1
+        across the pixel with 0.5, 0.5 being the center
+    */
+    pub fn ray_for_pixel_offset(&self, x: u32, y: u32, dx: Float, dy: Float) -> Ray {
+        // offset from the edge of the canvas to the point in the pixel
+        let xoffset = (x as Float + dx) * self.pixel_size;
+        let yoffset = (y as Float + dy) * self.pixel_size;
 
         // the untransformed coordinates of the pixel in world space
         // the camera looks toward -z so +x is to the left;
@@ -55,20 +69,25 @@ impl Camera {
         let pixel = inverse.multiply_tuple(&point!(world_x, world_y, -1));
         let origin = inverse.multiply_tuple(&point!(0,0,0));
         let direction = pixel.subtract(&origin).normalize();
-        Ray::new(origin, direction)
+        Ray::at_time(origin, direction, self.shutter_time(0.5))
+    }
+
+    // the time a fraction 0 to 1 of the way through the shutter interval
+    pub fn shutter_time(&self, fraction: Float) -> Float {
+        self.shutter_open + (self.shutter_close - self.shutter_open) * fraction
     }
 }
 
 #[test]
 fn camera_test() {
-    let half_pi = std::f32::consts::PI / 2.0;
-    let quarter_pi = std::f32::consts::PI / 4.0;
+    let half_pi = crate::math::consts::PI / 2.0;
+    let quarter_pi = crate::math::consts::PI / 4.0;
 
     let camera = Camera::new(200, 125, half_pi);
-    assert_eq!(0.01, camera.pixel_size);
+    assert!(crate::math::fequal(0.01, camera.pixel_size));
 
     let camera = Camera::new(125, 200, half_pi);
-    assert_eq!(0.01, camera.pixel_size);
+    assert!(crate::math::fequal(0.01, camera.pixel_size));
 
     let camera = Camera::new(201, 101, half_pi);
     let ray = camera.ray_for_pixel(100, 50);
@@ -83,5 +102,17 @@ fn camera_test() {
     camera.transform = Matrix4x4::rotation_y(quarter_pi).multiply(&Matrix4x4::translation(0.0, -2.0, 5.0));
     let ray = camera.ray_for_pixel(100, 50);
     assert_eq!(ray.origin, point!(0, 2, -5));
-    assert_eq!(ray.direction, vector!(2_f32.sqrt()/2.0, 0, -2_f32.sqrt()/2.0));
-}
\ No newline at end of file
+    assert_eq!(ray.direction, vector!((2.0 as Float).sqrt()/2.0, 0, -(2.0 as Float).sqrt()/2.0));
+}
+
+#[test]
+fn ray_for_pixel_offset_test() {
+    let camera = Camera::new(201, 101, crate::math::consts::PI / 2.0);
+    let center = camera.ray_for_pixel(0, 0);
+    assert_eq!(center.direction, camera.ray_for_pixel_offset(0, 0, 0.5, 0.5).direction);
+
+    // the top left corner of the canvas
+    let corner = camera.ray_for_pixel_offset(0, 0, 0.0, 0.0);
+    let expected = vector!(1, 101.0 / 201.0, -1).normalize();
+    assert_eq!(corner.direction, expected);
+}
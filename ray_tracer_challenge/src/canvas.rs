@@ -1,5 +1,7 @@
 use crate::color::Color;
+use crate::math::Float;
 
+#[derive(Clone)]
 pub struct Canvas {
     width: u32,
     height: u32,
@@ -21,20 +23,261 @@ impl Canvas {
         }
     }
 
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // a canvas of the same size with f applied to every pixel
+    pub fn map<F: Fn(&Color) -> Color>(&self, f: F) -> Canvas {
+        Canvas {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(f).collect()
+        }
+    }
+
     pub fn set_pixel(&mut self, x: u32, y: u32, color: &Color) {
         let index = (y * self.width + x) as usize;
         self.pixels[index] = color.clone();
     }
     
-    pub fn get_pixel(&mut self, x: u32,  y: u32) -> Color {
+    pub fn get_pixel(&self, x: u32,  y: u32) -> Color {
         let index = (y * self.width + x) as usize;
         self.pixels[index].clone()
     }
+
+    // the pixels a row at a time, top row first
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        self.pixels.chunks(self.width.max(1) as usize)
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Color]> {
+        self.pixels.chunks_mut(self.width.max(1) as usize)
+    }
+
+    /*
+        copies source over this canvas with its top left corner at x, y,
+        whatever falls outside this canvas is left out
+    */
+    pub fn blit(&mut self, source: &Canvas, x: u32, y: u32) {
+        let width = source.width.min(self.width.saturating_sub(x));
+        let height = source.height.min(self.height.saturating_sub(y));
+        if width == 0 || height == 0 {
+            return;
+        }
+        for row in 0..height {
+            let from = (row * source.width) as usize;
+            let to = ((y + row) * self.width + x) as usize;
+            self.pixels[to..to + width as usize].clone_from_slice(&source.pixels[from..from + width as usize]);
+        }
+    }
+
+    // the width by height pixels from x, y, clipped to the canvas
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Canvas {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let mut canvas = Canvas::new(width, height);
+        for (row, pixels) in canvas.rows_mut().enumerate() {
+            let from = ((y + row as u32) * self.width + x) as usize;
+            pixels.clone_from_slice(&self.pixels[from..from + width as usize]);
+        }
+        canvas
+    }
+
+    // an empty canvas has nothing to sample and resizes to black
+    pub fn resize(&self, width: u32, height: u32, filter: Resample) -> Canvas {
+        if self.width == 0 || self.height == 0 || width == 0 || height == 0 {
+            return Canvas::new(width, height);
+        }
+        match filter {
+            Resample::Box => {
+                // separably, first the rows then the columns
+                let columns = box_weights(self.width, width);
+                let rows = box_weights(self.height, height);
+                let mut across = Canvas::new(width, self.height);
+                for y in 0..self.height {
+                    for (x, weights) in columns.iter().enumerate() {
+                        let color = weighted_sum(weights, |i| &self.pixels[(y * self.width + i) as usize]);
+                        across.set_pixel(x as u32, y, &color);
+                    }
+                }
+                let mut canvas = Canvas::new(width, height);
+                for (y, weights) in rows.iter().enumerate() {
+                    for x in 0..width {
+                        let color = weighted_sum(weights, |i| &across.pixels[(i * width + x) as usize]);
+                        canvas.set_pixel(x, y as u32, &color);
+                    }
+                }
+                canvas
+            },
+            Resample::Bilinear => {
+                let mut canvas = Canvas::new(width, height);
+                for y in 0..height {
+                    for x in 0..width {
+                        let u = (x as Float + 0.5) / width as Float;
+                        let v = 1.0 - (y as Float + 0.5) / height as Float;
+                        canvas.set_pixel(x, y, &self.sample_bilinear(u, v));
+                    }
+                }
+                canvas
+            }
+        }
+    }
+
+    /*
+        convolves with kernel along the rows and then the columns,
+        the kernel has an odd length and is centered on each pixel,
+        pixels past the edges repeat the edge pixel
+    */
+    pub fn convolve_separable(&self, kernel: &[Float]) -> Canvas {
+        let radius = (kernel.len() / 2) as i64;
+        let clamp = |i: i64, size: u32| i.clamp(0, size as i64 - 1) as u32;
+        let mut across = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut total = Color::BLACK;
+                for (k, weight) in kernel.iter().enumerate() {
+                    let sx = clamp(x as i64 + k as i64 - radius, self.width);
+                    total = total.add(&self.pixels[(y * self.width + sx) as usize].multiplyf(*weight));
+                }
+                across.set_pixel(x, y, &total);
+            }
+        }
+        let mut canvas = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut total = Color::BLACK;
+                for (k, weight) in kernel.iter().enumerate() {
+                    let sy = clamp(y as i64 + k as i64 - radius, self.height);
+                    total = total.add(&across.pixels[(sy * self.width + x) as usize].multiplyf(*weight));
+                }
+                canvas.set_pixel(x, y, &total);
+            }
+        }
+        canvas
+    }
+
+    // sigma in pixels, 0 or less leaves the image as it is
+    pub fn gaussian_blur(&self, sigma: Float) -> Canvas {
+        if sigma <= 0.0 {
+            return self.clone();
+        }
+        self.convolve_separable(&gaussian_kernel(sigma))
+    }
+
+    /*
+        light from pixels brighter than threshold spreading onto
+        their neighbours, as in a camera lens
+
+        the part of each pixel above threshold is blurred by sigma
+        and added back scaled by strength, so it only does anything
+        to high dynamic range renders with threshold at 1 or more
+    */
+    pub fn bloom(&self, threshold: Float, sigma: Float, strength: Float) -> Canvas {
+        let bright = self.map(|color| {
+            let peak = color.red.max(color.green).max(color.blue);
+            if peak > threshold {
+                color.multiplyf((peak - threshold) / peak)
+            }
+            else {
+                Color::BLACK
+            }
+        });
+        let glow = bright.gaussian_blur(sigma);
+        let mut canvas = self.clone();
+        for (pixel, light) in canvas.pixels.iter_mut().zip(glow.pixels.iter()) {
+            *pixel = pixel.add(&light.multiplyf(strength));
+        }
+        canvas
+    }
+
+    /*
+        texture lookups, u and v run 0 to 1 across the canvas
+        with v = 0 at the bottom row, outside values are clamped
+
+        pixel centers sit at half texel offsets so each pixel
+        covers an equal share of the 0 to 1 range, an empty canvas
+        is black everywhere
+    */
+    pub fn sample_nearest(&self, u: Float, v: Float) -> Color {
+        if self.width == 0 || self.height == 0 {
+            return Color::BLACK;
+        }
+        let u = u.clamp(0.0, 1.0);
+        let v = v.clamp(0.0, 1.0);
+        let x = ((u * self.width as Float) as u32).min(self.width - 1);
+        let y = (((1.0 - v) * self.height as Float) as u32).min(self.height - 1);
+        self.pixels[(y * self.width + x) as usize].clone()
+    }
+
+    pub fn sample_bilinear(&self, u: Float, v: Float) -> Color {
+        if self.width == 0 || self.height == 0 {
+            return Color::BLACK;
+        }
+        let x = (u * self.width as Float - 0.5).clamp(0.0, (self.width - 1) as Float);
+        let y = ((1.0 - v) * self.height as Float - 0.5).clamp(0.0, (self.height - 1) as Float);
+        let x0 = x.floor() as u32;
+        let y0 = y.floor() as u32;
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let tx = x - x.floor();
+        let ty = y - y.floor();
+        let texel = |x: u32, y: u32| &self.pixels[(y * self.width + x) as usize];
+        let top = texel(x0, y0).multiplyf(1.0 - tx).add(&texel(x1, y0).multiplyf(tx));
+        let bottom = texel(x0, y1).multiplyf(1.0 - tx).add(&texel(x1, y1).multiplyf(tx));
+        top.multiplyf(1.0 - ty).add(&bottom.multiplyf(ty))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Resample {
+    // the mean of the source pixels each pixel covers, best for shrinking
+    Box,
+    // interpolated at each pixel center, see Canvas::sample_bilinear
+    Bilinear
 }
 
+/*
+    for each of the to pixels along an axis, the from pixels it
+    covers and how much of each, the weights add up to 1
+*/
+fn box_weights(from: u32, to: u32) -> Vec<Vec<(u32, Float)>> {
+    let scale = from as Float / to as Float;
+    (0..to).map(|i| {
+        let start = i as Float * scale;
+        let end = ((i + 1) as Float * scale).min(from as Float);
+        let first = start.floor() as u32;
+        let last = (end.ceil() as u32).clamp(first + 1, from);
+        (first..last)
+            .map(|j| {
+                let covered = end.min((j + 1) as Float) - start.max(j as Float);
+                (j, covered / (end - start))
+            })
+            .collect()
+    }).collect()
+}
+
+fn weighted_sum<'a, F: Fn(u32) -> &'a Color>(weights: &[(u32, Float)], pixel: F) -> Color {
+    weights.iter().fold(Color::BLACK, |total, (i, weight)| total.add(&pixel(*i).multiplyf(*weight)))
+}
 
+// normalized weights out to three sigma either side
+pub fn gaussian_kernel(sigma: Float) -> Vec<Float> {
+    let radius = (3.0 * sigma).ceil().max(1.0) as i32;
+    let weights: Vec<Float> = (-radius..=radius)
+        .map(|i| (-((i * i) as Float) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: Float = weights.iter().sum();
+    weights.iter().map(|w| w / total).collect()
+}
 
-fn clamp_255(v: f32) -> i32 {
+fn clamp_255(v: Float) -> i32 {
     let mut a: i32 = (255.0 * v) as i32;
     if a < 0 {
         a = 0;
@@ -101,6 +344,299 @@ pub fn to_ppm(canvas : &Canvas) -> String {
     s
 }
 
+/*
+    how far apart two images of the same size are, errors are
+    per channel absolute differences
+*/
+pub struct CanvasDiff {
+    // root mean square error over every channel of every pixel
+    pub rmse: Float,
+    // peak signal to noise ratio in decibels for a peak of 1, infinite for identical images
+    pub psnr: Float,
+    pub max_error: Float,
+    // the first pixel with the largest error
+    pub max_error_at: (u32, u32),
+    // the absolute difference of each channel
+    pub image: Canvas
+}
+
+pub fn diff(a: &Canvas, b: &Canvas) -> Result<CanvasDiff, String> {
+    if a.width != b.width || a.height != b.height {
+        return Err(format!("can't compare a {} x {} canvas with a {} x {} one", a.width, a.height, b.width, b.height));
+    }
+    let mut image = Canvas::new(a.width, a.height);
+    let mut sum_squares = 0.0;
+    let mut max_error = 0.0;
+    let mut max_error_at = (0, 0);
+    for (index, (pa, pb)) in a.pixels.iter().zip(b.pixels.iter()).enumerate() {
+        let error = Color::rgb(
+            (pa.red - pb.red).abs(),
+            (pa.green - pb.green).abs(),
+            (pa.blue - pb.blue).abs());
+        sum_squares += error.red * error.red + error.green * error.green + error.blue * error.blue;
+        let largest = error.red.max(error.green).max(error.blue);
+        if largest > max_error {
+            max_error = largest;
+            max_error_at = (index as u32 % a.width, index as u32 / a.width);
+        }
+        image.pixels[index] = error;
+    }
+    let channels = (a.pixels.len() * 3).max(1) as Float;
+    let rmse = (sum_squares / channels).sqrt();
+    Ok(CanvasDiff {
+        rmse,
+        psnr: if rmse > 0.0 { -20.0 * rmse.log10() } else { Float::INFINITY },
+        max_error,
+        max_error_at,
+        image
+    })
+}
+
+/*
+    portable float map, the raw colors as little endian f32
+    with the bottom row first, nothing is clamped
+*/
+// the cast does something when Float is f64
+#[allow(clippy::unnecessary_cast)]
+pub fn to_pfm(canvas: &Canvas) -> Vec<u8> {
+    let mut data = format!("PF\n{} {}\n-1.0\n", canvas.width, canvas.height).into_bytes();
+    for y in (0..canvas.height).rev() {
+        for x in 0..canvas.width {
+            let c = &canvas.pixels[(y * canvas.width + x) as usize];
+            for channel in [c.red, c.green, c.blue].iter() {
+                data.extend_from_slice(&(*channel as f32).to_le_bytes());
+            }
+        }
+    }
+    data
+}
+
+/*
+    radiance .hdr, each pixel is an 8 bit mantissa per channel
+    sharing an 8 bit exponent, so very bright values survive at
+    about 1% precision, negative values are written as 0
+
+    scanlines use the run length layout with literal runs only,
+    except where the width is outside what that layout allows
+*/
+pub fn to_rgbe(canvas: &Canvas) -> Vec<u8> {
+    let mut data = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        canvas.height,
+        canvas.width).into_bytes();
+    let run_length = (8..0x8000).contains(&canvas.width);
+    for y in 0..canvas.height {
+        let row = &canvas.pixels[(y * canvas.width) as usize..((y + 1) * canvas.width) as usize];
+        let pixels: Vec<[u8; 4]> = row.iter().map(rgbe).collect();
+        if run_length {
+            data.extend_from_slice(&[2, 2, (canvas.width >> 8) as u8, (canvas.width & 0xFF) as u8]);
+            for channel in 0..4 {
+                for chunk in pixels.chunks(128) {
+                    data.push(chunk.len() as u8);
+                    data.extend(chunk.iter().map(|p| p[channel]));
+                }
+            }
+        }
+        else {
+            for p in pixels.iter() {
+                data.extend_from_slice(p);
+            }
+        }
+    }
+    data
+}
+
+fn rgbe(color: &Color) -> [u8; 4] {
+    // the largest value the 8 bit exponent holds, infinities are cut
+    // to it and max drops NaNs in favour of 0
+    let largest = (2.0 as Float).powi(127) * (255.0 / 256.0);
+    let r = color.red.max(0.0).min(largest);
+    let g = color.green.max(0.0).min(largest);
+    let b = color.blue.max(0.0).min(largest);
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in 0.5 to 1
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / (2.0 as Float).powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / (2.0 as Float).powi(e);
+    [(r * scale) as u8, (g * scale) as u8, (b * scale) as u8, (e + 128).clamp(0, 255) as u8]
+}
+
+/*
+    8 bit rgb png clamped like to_ppm, the image data is stored
+    uncompressed inside the zlib stream to keep this self contained
+*/
+pub fn to_png(canvas: &Canvas) -> Vec<u8> {
+    // each row starts with filter type 0, none
+    let mut raw = Vec::with_capacity(((canvas.width * 3 + 1) * canvas.height) as usize);
+    for y in 0..canvas.height {
+        raw.push(0);
+        for x in 0..canvas.width {
+            let c = &canvas.pixels[(y * canvas.width + x) as usize];
+            raw.push(clamp_255(c.red) as u8);
+            raw.push(clamp_255(c.green) as u8);
+            raw.push(clamp_255(c.blue) as u8);
+        }
+    }
+
+    // zlib header, stored deflate blocks of at most 65535 bytes, adler32
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = if raw.is_empty() { vec![&raw[..]] } else { raw.chunks(0xFFFF).collect() };
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push(if i + 1 == blocks.len() { 1 } else { 0 });
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&canvas.width.to_be_bytes());
+    header.extend_from_slice(&canvas.height.to_be_bytes());
+    // bit depth 8, color type 2 (rgb), default compression, filtering and no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _bit in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/*
+    reads plain (P3) and binary (P6) ppm images
+
+    values are scaled by the max color value in the header
+    so any bit depth comes back in 0 to 1
+*/
+pub fn from_ppm(data: &[u8]) -> Result<Canvas, String> {
+    let mut reader = PpmReader { data, position: 0 };
+    let magic = reader.token()?;
+    if magic != "P3" && magic != "P6" {
+        return Err(format!("unsupported ppm format {}", magic));
+    }
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max = reader.number()?;
+    if max == 0 || max > 65535 {
+        return Err(format!("invalid max color value {}", max));
+    }
+    if width == 0 || height == 0 {
+        return Err(format!("invalid ppm size {} x {}", width, height));
+    }
+    // every value takes at least a byte, so a header asking for more than
+    // the data holds is rejected before anything is allocated
+    let bytes_per_value = if max > 255 { 2 } else { 1 };
+    let values = (width as usize).checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .filter(|values| *values <= (data.len() - reader.position) / bytes_per_value)
+        .ok_or_else(|| format!("ppm pixel data is truncated for {} x {}", width, height))?;
+    let scale = max as Float;
+    let mut canvas = Canvas::new(width, height);
+
+    if magic == "P3" {
+        for index in 0..canvas.pixels.len() {
+            let r = reader.number()? as Float / scale;
+            let g = reader.number()? as Float / scale;
+            let b = reader.number()? as Float / scale;
+            canvas.pixels[index] = Color::rgb(r, g, b);
+        }
+    }
+    else {
+        // a single whitespace byte separates the header from the pixels
+        let start = reader.position + 1;
+        let expected = values * bytes_per_value;
+        if data.len() < start + expected {
+            return Err(String::from("ppm pixel data is truncated"));
+        }
+        let values = &data[start..start + expected];
+        let value = |i: usize| -> Float {
+            if bytes_per_value == 2 {
+                ((values[i * 2] as u32) << 8 | values[i * 2 + 1] as u32) as Float / scale
+            }
+            else {
+                values[i] as Float / scale
+            }
+        };
+        for index in 0..canvas.pixels.len() {
+            canvas.pixels[index] = Color::rgb(value(index * 3), value(index * 3 + 1), value(index * 3 + 2));
+        }
+    }
+    Ok(canvas)
+}
+
+struct PpmReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> PpmReader<'a> {
+    // next whitespace separated token, skipping # comments
+    fn token(&mut self) -> Result<String, String> {
+        loop {
+            while self.position < self.data.len() && self.data[self.position].is_ascii_whitespace() {
+                self.position += 1;
+            }
+            if self.position < self.data.len() && self.data[self.position] == b'#' {
+                while self.position < self.data.len() && self.data[self.position] != b'\n' {
+                    self.position += 1;
+                }
+            }
+            else {
+                break;
+            }
+        }
+        let start = self.position;
+        while self.position < self.data.len() && !self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(String::from("unexpected end of ppm data"));
+        }
+        Ok(String::from_utf8_lossy(&self.data[start..self.position]).to_string())
+    }
+
+    fn number(&mut self) -> Result<u32, String> {
+        let token = self.token()?;
+        token.parse::<u32>().map_err(|_| format!("expected a number in ppm data, found {}", token))
+    }
+}
+
 #[test]
 fn create_canvas_test() {
     let a = Canvas::new(10, 20);
@@ -135,4 +671,269 @@ fn to_ppm_test() {
     let canvas = Canvas::new(5, 3);
     let s = to_ppm(&canvas);
     println!("{}",s);
-}
\ No newline at end of file
+}
+
+#[test]
+fn from_ppm_test() {
+    // wrong magic number
+    assert!(from_ppm(b"P32\n1 1\n255\n0 0 0\n").is_err());
+
+    // comments are skipped and values are scaled by the max
+    let ppm = b"P3\n# made by hand\n2 2\n100\n100 100 100  50 50 50\n# second row\n0 50 100  100 0 0\n";
+    let canvas = from_ppm(ppm).unwrap();
+    assert_eq!(canvas.width, 2);
+    assert_eq!(canvas.height, 2);
+    assert_eq!(canvas.get_pixel(0, 0), Color::rgb(1.0, 1.0, 1.0));
+    assert_eq!(canvas.get_pixel(1, 0), Color::rgb(0.5, 0.5, 0.5));
+    assert_eq!(canvas.get_pixel(0, 1), Color::rgb(0.0, 0.5, 1.0));
+    assert_eq!(canvas.get_pixel(1, 1), Color::rgb(1.0, 0.0, 0.0));
+
+    // missing pixels
+    assert!(from_ppm(b"P3\n2 1\n255\n0 0 0\n").is_err());
+    // sizes the data can't hold, or no pixels at all
+    assert!(from_ppm(b"P6\n100000 100000\n255\n").is_err());
+    assert!(from_ppm(b"P3\n0 1\n255\n").is_err());
+
+    // binary
+    let mut ppm = b"P6\n2 1\n255\n".to_vec();
+    ppm.extend_from_slice(&[255, 0, 0, 0, 51, 255]);
+    let canvas = from_ppm(&ppm).unwrap();
+    assert_eq!(canvas.get_pixel(0, 0), Color::rgb(1.0, 0.0, 0.0));
+    assert_eq!(canvas.get_pixel(1, 0), Color::rgb(0.0, 0.2, 1.0));
+
+    // whatever to_ppm writes can be read back
+    let mut canvas = Canvas::new(7, 3);
+    canvas.set_pixel(6, 2, &Color::rgb(1.0, 0.2, 0.6));
+    let read = from_ppm(to_ppm(&canvas).as_bytes()).unwrap();
+    assert_eq!(read.get_pixel(6, 2), Color::rgb(1.0, 0.2, 0.6));
+    assert_eq!(read.get_pixel(0, 0), Color::BLACK);
+}
+
+#[test]
+fn sample_test() {
+    let mut canvas = Canvas::new(3, 2);
+    canvas.set_pixel(0, 0, &Color::WHITE);
+    canvas.set_pixel(2, 1, &Color::rgb(0.0, 1.0, 0.0));
+
+    // v = 1 is the top row
+    assert_eq!(canvas.sample_nearest(0.0, 1.0), Color::WHITE);
+    assert_eq!(canvas.sample_nearest(1.0, 0.0), Color::rgb(0.0, 1.0, 0.0));
+    assert_eq!(canvas.sample_nearest(0.2, 0.7), Color::WHITE);
+    assert_eq!(canvas.sample_nearest(0.4, 0.7), Color::BLACK);
+    // clamped outside 0 to 1
+    assert_eq!(canvas.sample_nearest(-3.0, 2.0), Color::WHITE);
+
+    assert_eq!(canvas.sample_bilinear(0.0, 1.0), Color::WHITE);
+    assert_eq!(canvas.sample_bilinear(1.0 / 3.0, 1.0), Color::rgb(0.5, 0.5, 0.5));
+    assert_eq!(canvas.sample_bilinear(1.0 / 3.0, 0.5), Color::rgb(0.25, 0.25, 0.25));
+    assert_eq!(canvas.sample_bilinear(1.0, 0.5), Color::rgb(0.0, 0.5, 0.0));
+
+    // nothing to sample
+    for empty in [Canvas::new(0, 0), Canvas::new(0, 3), Canvas::new(3, 0)].iter() {
+        assert_eq!(empty.sample_nearest(0.5, 0.5), Color::BLACK);
+        assert_eq!(empty.sample_bilinear(0.5, 0.5), Color::BLACK);
+    }
+}
+
+#[test]
+fn to_pfm_test() {
+    let mut canvas = Canvas::new(2, 2);
+    canvas.set_pixel(0, 0, &Color::rgb(5.0, 0.5, -1.0));
+    let data = to_pfm(&canvas);
+    let header = b"PF\n2 2\n-1.0\n";
+    assert_eq!(&data[..header.len()], header);
+    assert_eq!(data.len(), header.len() + 2 * 2 * 3 * 4);
+    // the top left pixel is the first one of the last row written
+    let value = |i: usize| {
+        let start = header.len() + i * 4;
+        f32::from_le_bytes([data[start], data[start + 1], data[start + 2], data[start + 3]])
+    };
+    assert_eq!(value(6), 5.0);
+    assert_eq!(value(7), 0.5);
+    assert_eq!(value(8), -1.0);
+    assert_eq!(value(0), 0.0);
+}
+
+#[test]
+fn to_rgbe_test() {
+    assert_eq!(rgbe(&Color::rgb(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+    assert_eq!(rgbe(&Color::rgb(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    assert_eq!(rgbe(&Color::rgb(10.0, 0.0, -3.0)), [160, 0, 0, 132]);
+    assert_eq!(rgbe(&Color::rgb(Float::INFINITY, Float::NAN, 0.0)), [255, 0, 0, 255]);
+    assert_eq!(rgbe(&Color::rgb(Float::NAN, Float::NAN, Float::NAN)), [0, 0, 0, 0]);
+
+    // narrow images are written flat
+    let mut canvas = Canvas::new(2, 1);
+    canvas.set_pixel(1, 0, &Color::rgb(1.0, 0.5, 0.0));
+    let data = to_rgbe(&canvas);
+    let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+    assert_eq!(&data[..header.len()], header);
+    assert_eq!(&data[header.len()..], &[0, 0, 0, 0, 128, 64, 0, 129]);
+
+    // wider ones as literal runs, one channel after another
+    let mut canvas = Canvas::new(8, 1);
+    canvas.set_pixel(7, 0, &Color::rgb(1.0, 0.5, 0.0));
+    let data = to_rgbe(&canvas);
+    let body = &data[data.len() - 40..];
+    assert_eq!(&body[..4], &[2, 2, 0, 8]);
+    assert_eq!(&body[4..13], &[8, 0, 0, 0, 0, 0, 0, 0, 128]);
+    assert_eq!(&body[31..40], &[8, 0, 0, 0, 0, 0, 0, 0, 129]);
+
+    // an infinite pixel comes out as bright as it can be, a NaN one black
+    let mut canvas = Canvas::new(2, 1);
+    canvas.set_pixel(0, 0, &Color::rgb(Float::INFINITY, 1.0, 1.0));
+    canvas.set_pixel(1, 0, &Color::rgb(Float::NAN, Float::NAN, Float::NAN));
+    let data = to_rgbe(&canvas);
+    assert_eq!(&data[data.len() - 8..], &[255, 0, 0, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn to_png_test() {
+    let mut canvas = Canvas::new(2, 1);
+    canvas.set_pixel(0, 0, &Color::rgb(1.0, 0.0, 0.5));
+    let png = to_png(&canvas);
+    assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+    // IHDR with its known crc for a 2 x 1 8 bit rgb image
+    assert_eq!(&png[8..16], &[0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+    assert_eq!(&png[16..29], &[0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+    assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+    // the pixels sit uncompressed in the single stored block
+    let idat = &png[33..png.len() - 12];
+    assert_eq!(&idat[4..8], b"IDAT");
+    assert_eq!(&idat[8..15], &[0x78, 0x01, 1, 7, 0, 0xF8, 0xFF]);
+    assert_eq!(&idat[15..22], &[0, 255, 0, 127, 0, 0, 0]);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+}
+
+#[test]
+fn diff_test() {
+    let mut a = Canvas::new(3, 2);
+    let mut b = Canvas::new(3, 2);
+    let same = diff(&a, &b).unwrap();
+    assert_eq!(same.rmse, 0.0);
+    assert_eq!(same.psnr, Float::INFINITY);
+    assert_eq!(same.max_error, 0.0);
+
+    a.set_pixel(2, 1, &Color::rgb(0.5, 0.1, 0.0));
+    b.set_pixel(2, 1, &Color::rgb(0.0, 0.2, 0.0));
+    b.set_pixel(0, 0, &Color::rgb(0.0, 0.0, 0.2));
+    let d = diff(&a, &b).unwrap();
+    assert!(crate::math::fequal(d.max_error, 0.5));
+    assert_eq!(d.max_error_at, (2, 1));
+    assert_eq!(d.image.get_pixel(2, 1), Color::rgb(0.5, 0.1, 0.0));
+    assert_eq!(d.image.get_pixel(0, 0), Color::rgb(0.0, 0.0, 0.2));
+    // (0.25 + 0.01 + 0.04) / 18 channels
+    assert!(crate::math::fequal(d.rmse, (0.3 as Float / 18.0).sqrt()));
+    assert!(crate::math::fequal(d.psnr, -20.0 * d.rmse.log10()));
+
+    assert!(diff(&a, &Canvas::new(2, 3)).is_err());
+}
+
+#[test]
+fn rows_blit_crop_test() {
+    let mut canvas = Canvas::new(4, 3);
+    canvas.set_pixel(1, 2, &Color::WHITE);
+    let rows: Vec<&[Color]> = canvas.rows().collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[2][1], Color::WHITE);
+    for pixel in canvas.rows_mut().next().unwrap() {
+        *pixel = Color::rgb(0.0, 1.0, 0.0);
+    }
+    assert_eq!(canvas.get_pixel(3, 0), Color::rgb(0.0, 1.0, 0.0));
+
+    // hanging off the bottom right, only the top left pixel lands
+    let mut source = Canvas::new(2, 2);
+    source.set_pixel(0, 0, &Color::rgb(1.0, 0.0, 0.0));
+    source.set_pixel(1, 1, &Color::rgb(0.0, 0.0, 1.0));
+    canvas.blit(&source, 3, 2);
+    assert_eq!(canvas.get_pixel(3, 2), Color::rgb(1.0, 0.0, 0.0));
+    canvas.blit(&source, 1, 1);
+    assert_eq!(canvas.get_pixel(2, 2), Color::rgb(0.0, 0.0, 1.0));
+    canvas.blit(&source, 10, 10);
+    // off to the side of rows it overlaps
+    let before = canvas.clone();
+    canvas.blit(&source, 10, 1);
+    canvas.blit(&source, 1, 10);
+    assert_eq!(diff(&canvas, &before).unwrap().max_error, 0.0);
+
+    let crop = canvas.crop(2, 1, 5, 5);
+    assert_eq!((crop.width, crop.height), (2, 2));
+    assert_eq!(crop.get_pixel(0, 1), Color::rgb(0.0, 0.0, 1.0));
+    assert_eq!(crop.get_pixel(1, 1), Color::rgb(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn resize_test() {
+    let mut canvas = Canvas::new(4, 2);
+    canvas.set_pixel(0, 0, &Color::WHITE);
+    canvas.set_pixel(3, 1, &Color::rgb(0.0, 1.0, 0.0));
+
+    // halving averages 2 x 2 blocks
+    let half = canvas.resize(2, 1, Resample::Box);
+    assert_eq!(half.get_pixel(0, 0), Color::rgb(0.25, 0.25, 0.25));
+    assert_eq!(half.get_pixel(1, 0), Color::rgb(0.0, 0.25, 0.0));
+
+    // a third of a pixel wide each, the middle one straddles two
+    let row = canvas.crop(0, 0, 4, 1).resize(3, 1, Resample::Box);
+    assert_eq!(row.get_pixel(0, 0), Color::rgb(0.75, 0.75, 0.75));
+    assert_eq!(row.get_pixel(1, 0), Color::BLACK);
+
+    // box and bilinear agree at the same size
+    let same = canvas.resize(4, 2, Resample::Bilinear);
+    assert_eq!(diff(&same, &canvas).unwrap().max_error, 0.0);
+    let same = canvas.resize(4, 2, Resample::Box);
+    assert_eq!(diff(&same, &canvas).unwrap().max_error, 0.0);
+
+    let double = canvas.resize(8, 4, Resample::Bilinear);
+    assert_eq!(double.get_pixel(0, 0), Color::WHITE);
+    assert_eq!(double.get_pixel(1, 0), Color::rgb(0.75, 0.75, 0.75));
+
+    // nothing to sample from an empty canvas, or into one
+    for filter in [Resample::Box, Resample::Bilinear].iter().copied() {
+        let black = Canvas::new(0, 2).resize(3, 2, filter);
+        assert_eq!((black.width(), black.height()), (3, 2));
+        assert_eq!(black.get_pixel(2, 1), Color::BLACK);
+        assert_eq!(Canvas::new(4, 0).resize(2, 2, filter).get_pixel(1, 1), Color::BLACK);
+        assert_eq!(canvas.resize(0, 3, filter).height(), 3);
+    }
+}
+
+#[test]
+fn blur_test() {
+    let kernel = gaussian_kernel(1.0);
+    assert_eq!(kernel.len(), 7);
+    assert!(crate::math::fequal(kernel.iter().sum(), 1.0));
+    assert!(kernel[3] > kernel[2] && kernel[2] == kernel[4]);
+
+    // a box kernel smears a dot into a square, the edge repeats
+    let mut canvas = Canvas::new(5, 5);
+    canvas.set_pixel(2, 2, &Color::rgb(9.0, 0.0, 0.0));
+    canvas.set_pixel(0, 0, &Color::rgb(0.0, 3.0, 0.0));
+    let blurred = canvas.convolve_separable(&[1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+    assert!(crate::math::fequal(blurred.get_pixel(1, 3).red, 1.0));
+    assert_eq!(blurred.get_pixel(0, 2).red, 0.0);
+    assert!(crate::math::fequal(blurred.get_pixel(0, 0).green, 3.0 * 4.0 / 9.0));
+
+    // blurring keeps the total light away from the edges
+    let mut canvas = Canvas::new(9, 9);
+    canvas.set_pixel(4, 4, &Color::rgb(9.0, 0.0, 0.0));
+    let blurred = canvas.gaussian_blur(0.8);
+    let total: Float = blurred.pixels.iter().map(|c| c.red).sum();
+    assert!(crate::math::fequal(total, 9.0));
+    assert_eq!(diff(&canvas.gaussian_blur(0.0), &canvas).unwrap().max_error, 0.0);
+}
+
+#[test]
+fn bloom_test() {
+    let mut canvas = Canvas::new(9, 9);
+    canvas.set_pixel(4, 4, &Color::rgb(5.0, 5.0, 5.0));
+    canvas.set_pixel(0, 0, &Color::rgb(0.9, 0.9, 0.9));
+    let bloomed = canvas.bloom(1.0, 1.0, 0.5);
+
+    // light spills around the bright pixel but not from the dim one
+    assert!(bloomed.get_pixel(5, 4).red > 0.0);
+    assert!(bloomed.get_pixel(4, 4).red > 5.0);
+    assert_eq!(bloomed.get_pixel(0, 0), Color::rgb(0.9, 0.9, 0.9));
+    assert_eq!(bloomed.get_pixel(8, 0), Color::BLACK);
+}
@@ -1,15 +1,16 @@
 use crate::math::fequal;
+use crate::math::Float;
 use crate::rgb;
 
 #[derive(Debug, Clone)]
 pub struct Color {
-    pub red: f32,
-    pub green: f32,
-    pub blue: f32
+    pub red: Float,
+    pub green: Float,
+    pub blue: Float
 }
 
 impl Color {
-    pub fn rgb (r:f32, g:f32, b:f32) -> Color {
+    pub fn rgb (r:Float, g:Float, b:Float) -> Color {
         Color {
             red: r,
             green: g,
@@ -36,7 +37,7 @@ impl Color {
         }
     }
 
-    pub fn multiplyf(&self, rhs: f32) -> Color {
+    pub fn multiplyf(&self, rhs: Float) -> Color {
         Color {
             red: self.red * rhs,
             green: self.green * rhs,
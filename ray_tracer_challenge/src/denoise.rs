@@ -0,0 +1,153 @@
+use crate::aov::Aovs;
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::math::Float;
+
+/*
+    edge avoiding a-trous wavelet filter (Dammertz et al. 2010)
+
+    each pass blurs with a 5 x 5 B3 spline kernel whose taps are
+    spread 1, 2, 4, 8 ... pixels apart, so a few passes cover a wide
+    area cheaply, every tap is weighted down the more its color,
+    normal, depth and albedo differ from the pixel's own so noise is
+    smoothed within a surface but not across its edges
+
+    the sigmas set how big a difference is tolerated, smaller keeps
+    edges sharper but smooths less, the color sigma is halved every
+    pass as the noise left gets smaller
+*/
+pub struct Denoiser {
+    pub passes: u32,
+    pub color_sigma: Float,
+    pub normal_sigma: Float,
+    // relative to the nearer of the two depths
+    pub depth_sigma: Float,
+    pub albedo_sigma: Float
+}
+
+const KERNEL: [Float; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+impl Denoiser {
+    pub fn new() -> Denoiser {
+        Denoiser {
+            passes: 5,
+            color_sigma: 1.0,
+            normal_sigma: 0.3,
+            depth_sigma: 0.1,
+            albedo_sigma: 0.1
+        }
+    }
+
+    // canvas and aovs from aov::render_with_aovs
+    pub fn denoise(&self, canvas: &Canvas, aovs: &Aovs) -> Canvas {
+        let mut color = canvas.clone();
+        let mut color_sigma = self.color_sigma;
+        for pass in 0..self.passes {
+            color = self.pass(&color, aovs, 1 << pass, color_sigma);
+            color_sigma /= 2.0;
+        }
+        color
+    }
+
+    fn pass(&self, canvas: &Canvas, aovs: &Aovs, step: i64, color_sigma: Float) -> Canvas {
+        let width = canvas.width();
+        let height = canvas.height();
+        let mut filtered = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = canvas.get_pixel(x, y);
+                let normal = aovs.normal.get_pixel(x, y);
+                let depth = aovs.depth.get_pixel(x, y).red;
+                let albedo = aovs.albedo.get_pixel(x, y);
+
+                let mut total = Color::BLACK;
+                let mut total_weight = 0.0;
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    let sy = y as i64 + (j as i64 - 2) * step;
+                    if sy < 0 || sy >= height as i64 {
+                        continue;
+                    }
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let sx = x as i64 + (i as i64 - 2) * step;
+                        if sx < 0 || sx >= width as i64 {
+                            continue;
+                        }
+                        let (sx, sy) = (sx as u32, sy as u32);
+                        let sample = canvas.get_pixel(sx, sy);
+                        let sample_depth = aovs.depth.get_pixel(sx, sy).red;
+                        let nearer = depth.min(sample_depth).max(Float::EPSILON);
+                        let depth_difference = (depth - sample_depth) / nearer;
+                        let exponent =
+                            distance_squared(&color, &sample) / (color_sigma * color_sigma)
+                            + distance_squared(&normal, &aovs.normal.get_pixel(sx, sy)) / (self.normal_sigma * self.normal_sigma)
+                            + depth_difference * depth_difference / (self.depth_sigma * self.depth_sigma)
+                            + distance_squared(&albedo, &aovs.albedo.get_pixel(sx, sy)) / (self.albedo_sigma * self.albedo_sigma);
+                        let weight = kx * ky * (-exponent).exp();
+                        total = total.add(&sample.multiplyf(weight));
+                        total_weight += weight;
+                    }
+                }
+                // the pixel itself always has a weight, the middle tap
+                filtered.set_pixel(x, y, &total.multiplyf(1.0 / total_weight));
+            }
+        }
+        filtered
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Denoiser {
+        Denoiser::new()
+    }
+}
+
+fn distance_squared(a: &Color, b: &Color) -> Float {
+    let d = a.subtract(b);
+    d.red * d.red + d.green * d.green + d.blue * d.blue
+}
+
+#[test]
+fn denoise_test() {
+    use crate::canvas;
+    use crate::sampling::Rng;
+    use crate::rgb;
+
+    // two flat surfaces meeting at x = 8 with noise over them
+    let (width, height) = (16, 8);
+    let mut clean = Canvas::new(width, height);
+    let mut noisy = Canvas::new(width, height);
+    let mut aovs = Aovs::new(width, height);
+    let mut rng = Rng::new(3);
+    for y in 0..height {
+        for x in 0..width {
+            let (color, normal, depth) = if x < 8 {
+                (rgb!(0.8,0.2,0.2), rgb!(0,0,-1), 4.0)
+            }
+            else {
+                (rgb!(0.2,0.2,0.8), rgb!(1,0,0), 6.0)
+            };
+            clean.set_pixel(x, y, &color);
+            let noise = (rng.next_float() - 0.5) * 0.4;
+            noisy.set_pixel(x, y, &color.add(&rgb!(noise, noise, noise)));
+            aovs.normal.set_pixel(x, y, &normal);
+            aovs.depth.set_pixel(x, y, &rgb!(depth, depth, depth));
+            aovs.albedo.set_pixel(x, y, &color);
+        }
+    }
+
+    let denoised = Denoiser::new().denoise(&noisy, &aovs);
+    let before = canvas::diff(&noisy, &clean).unwrap();
+    let after = canvas::diff(&denoised, &clean).unwrap();
+    assert!(after.rmse < before.rmse / 2.0);
+
+    // nothing bleeds across the edge
+    for y in 0..height {
+        assert!(denoised.get_pixel(7, y).blue < 0.35);
+        assert!(denoised.get_pixel(8, y).red < 0.35);
+    }
+
+    // no passes leaves the image alone
+    let mut none = Denoiser::new();
+    none.passes = 0;
+    assert_eq!(canvas::diff(&none.denoise(&noisy, &aovs), &noisy).unwrap().max_error, 0.0);
+}
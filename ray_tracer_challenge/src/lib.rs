@@ -0,0 +1,60 @@
+/*
+    the ray tracer as a library, main.rs is one program using it
+
+    the types most scenes need are re-exported here and the modules
+    they live in are private, the public modules hold the renderers,
+    image output and the parts of a scene beyond those types,
+    rendering starts from render::render
+
+        use ray_tracer_challenge::{Camera, Canvas, Light, Shape, World};
+        use ray_tracer_challenge::render::{self, RenderSettings};
+        use ray_tracer_challenge::{point, rgb};
+
+    the point!, vector!, tuple! and rgb! macros are exported at the
+    top of the crate and expand to math::Float fields whichever
+    precision it was built with, they name tuple and color so those
+    stay public
+*/
+
+mod macro_def;
+pub mod math;
+pub mod tuple;
+pub mod color;
+pub mod canvas;
+pub mod matrix;
+mod quaternion;
+mod ray;
+mod shape;
+mod light;
+pub mod material;
+mod noise;
+pub mod texture;
+mod world;
+mod camera;
+pub mod background;
+mod sampling;
+mod path_tracer;
+pub mod render;
+pub mod occlusion;
+pub mod aov;
+pub mod denoise;
+pub mod media;
+pub mod animation;
+pub mod progressive;
+pub mod tonemap;
+pub mod stats;
+
+pub use camera::Camera;
+pub use canvas::Canvas;
+pub use color::Color;
+pub use light::Light;
+pub use material::Material;
+pub use material::Pattern;
+pub use matrix::Matrix4x4;
+pub use quaternion::Quaternion;
+pub use ray::Ray;
+pub use shape::Shape;
+pub use tuple::Point;
+pub use tuple::Tuple;
+pub use tuple::Vector;
+pub use world::World;
@@ -3,6 +3,9 @@ use crate::tuple::Point;
 use crate::tuple::Vector;
 use crate::material::Material;
 use crate::material::Pattern;
+use crate::material::ShadingModel;
+use crate::math::Float;
+use crate::math::consts;
 use crate::shape::Shape;
 use crate::point;
 use crate::vector;
@@ -23,6 +26,8 @@ impl Light {
     }
 }
 
+// lighting_occluded at time 0 without occlusion, the tests' shorthand
+#[cfg(test)]
 pub fn lighting(
     material: &Material,
     shape: &Shape,
@@ -30,20 +35,38 @@ pub fn lighting(
     point: &Point,
     eyev: &Vector,
     normalv: &Vector,
-    in_shadow: bool) -> Color {
+    light_transmittance: &Color) -> Color {
+    lighting_occluded(material, shape, light, point, eyev, normalv, light_transmittance, 1.0, 0.0)
+}
+
+/*
+    lighting with the ambient term scaled by visibility, the
+    unoccluded fraction from occlusion::AmbientOcclusion, and the
+    surface color taken from shape's pose at the ray's time
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn lighting_occluded(
+    material: &Material,
+    shape: &Shape,
+    light: &Light,
+    point: &Point,
+    eyev: &Vector,
+    normalv: &Vector,
+    light_transmittance: &Color,
+    visibility: Float,
+    time: Float) -> Color {
     
-    let color = match &material.pattern {
-        Some(pattern) => pattern.color_at(&shape, &point),
-        None => material.color.clone()
-    };
+    let color = material.color_at_time(shape, point, time);
 
     let effective_color = color.hadamard(&light.intensity);
+    // the part of the light that gets past anything in the way
+    let intensity = light.intensity.hadamard(light_transmittance);
 
     // vector to the light from the position
     let light_vector = light.position.subtract(&point).normalize();
 
     // ambient is applied generally regardless of direct light
-    let ambient = effective_color.multiplyf(material.ambient);
+    let ambient = effective_color.multiplyf(material.ambient * visibility);
     
     // light_dot_normal represents the cosine of the angle
     // between the two vectors, negative means the light is
@@ -53,12 +76,17 @@ pub fn lighting(
     let diffuse : Color;
     let specular : Color;
 
-    if in_shadow || light_dot_normal < 0.0 {
+    if *light_transmittance == Color::BLACK || light_dot_normal < 0.0 {
         diffuse = Color::BLACK;
         specular = Color::BLACK;
     }
+    else if let ShadingModel::Microfacet { roughness, metallic } = material.shading {
+        let (d, s) = microfacet(&color, roughness, metallic, &light_vector, eyev, normalv);
+        diffuse = d.hadamard(&intensity);
+        specular = s.hadamard(&intensity);
+    }
     else {
-        diffuse = effective_color.multiplyf(material.diffuse * light_dot_normal);
+        diffuse = color.hadamard(&intensity).multiplyf(material.diffuse * light_dot_normal);
 
         // compute the cosine of the angle between the reflection vector
         // and the eye vector, negative means the light reflects away from the eye
@@ -69,12 +97,60 @@ pub fn lighting(
         }
         else {
             let factor = reflect_dot_eye.powf(material.shininess);
-            specular = light.intensity.multiplyf(material.specular * factor);
+            specular = intensity.multiplyf(material.specular * factor);
         }
     }
     return ambient.add(&diffuse).add(&specular);
 }
 
+/*
+    Cook-Torrance with the GGX distribution, Smith-Schlick
+    geometry term and Schlick's fresnel approximation
+
+    returns the diffuse and specular light reflected towards the eye
+    for a unit intensity light, the brdf is scaled by pi so a white
+    matte surface facing the light reflects about what a phong
+    surface with diffuse 1 does
+*/
+fn microfacet(
+    base_color: &Color,
+    roughness: Float,
+    metallic: Float,
+    light_vector: &Vector,
+    eyev: &Vector,
+    normalv: &Vector) -> (Color, Color) {
+
+    // perfectly smooth surfaces would turn the highlight into a spike
+    let alpha = (roughness * roughness).max(0.001);
+    let alpha2 = alpha * alpha;
+
+    let halfway = light_vector.add(eyev).normalize();
+    let n_dot_l = normalv.dot(light_vector).max(0.0);
+    let n_dot_v = normalv.dot(eyev).max(0.0001);
+    let n_dot_h = normalv.dot(&halfway).max(0.0);
+    let v_dot_h = eyev.dot(&halfway).max(0.0);
+
+    // share of microfacets facing along the halfway vector
+    let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    let distribution = alpha2 / (consts::PI * d * d);
+
+    // share of those neither shadowed nor masked by their neighbours
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let g1 = |cos: Float| cos / (cos * (1.0 - k) + k);
+    let geometry = g1(n_dot_l) * g1(n_dot_v);
+
+    // dielectrics reflect about 4% head on, metals tint the reflection
+    let f0 = rgb!(0.04,0.04,0.04).multiplyf(1.0 - metallic).add(&base_color.multiplyf(metallic));
+    let fresnel = f0.add(&Color::WHITE.subtract(&f0).multiplyf((1.0 - v_dot_h).powi(5)));
+
+    let specular = fresnel.multiplyf(distribution * geometry / (4.0 * n_dot_v) * consts::PI);
+    // what is not reflected at the surface scatters below it, except in metals
+    let diffuse = Color::WHITE.subtract(&fresnel)
+        .hadamard(base_color)
+        .multiplyf((1.0 - metallic) * n_dot_l);
+    (diffuse, specular)
+}
+
 #[test]
 fn lighting_test_eye_between_light_and_surface() {
      let sphere = Shape::sphere();
@@ -83,8 +159,8 @@ fn lighting_test_eye_between_light_and_surface() {
     let eye = vector!(0,0,-1);
     let normal = vector!(0,0,-1);
     let light = Light::point_light(point!(0,0,-10), rgb!(1,1,1));
-    let in_shadow = false;
-    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, in_shadow);
+    let light_transmittance = Color::WHITE;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &light_transmittance);
     assert_eq!(result, rgb!(1.9,1.9,1.9));
 }
 
@@ -93,11 +169,11 @@ fn lighting_test_eye_offset_between_light_and_surface() {
      let sphere = Shape::sphere();
      let material = Material::new();
     let position = point!(0,0,0);
-    let eye = vector!(0, 2_f32.sqrt()/2.0, -2_f32.sqrt()/2.0);
+    let eye = vector!(0, (2.0 as crate::math::Float).sqrt()/2.0, -(2.0 as crate::math::Float).sqrt()/2.0);
     let normal = vector!(0,0,-1);
     let light = Light::point_light(point!(0,0,-10), rgb!(1,1,1));
-    let in_shadow = false;
-    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, in_shadow);
+    let light_transmittance = Color::WHITE;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &light_transmittance);
     assert_eq!(result, rgb!(1,1,1));
 }
 
@@ -109,8 +185,8 @@ fn lighting_test_eye_offset_between_light_and_surface() {
      let eye = vector!(0,0,-1);
      let normal = vector!(0,0,-1);
      let light = Light::point_light(point!(0,10,-10), rgb!(1,1,1));
-    let in_shadow = false;
-    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, in_shadow);
+    let light_transmittance = Color::WHITE;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &light_transmittance);
      assert_eq!(result, rgb!(0.7364, 0.7364, 0.7364));
  }
 
@@ -119,11 +195,11 @@ fn lighting_test_eye_offset_between_light_and_surface() {
      let sphere = Shape::sphere();
      let material = Material::new();
      let position = point!(0,0,0);
-     let eye = vector!(0, -2_f32.sqrt()/2.0, -2_f32.sqrt()/2.0);
+     let eye = vector!(0, -(2.0 as crate::math::Float).sqrt()/2.0, -(2.0 as crate::math::Float).sqrt()/2.0);
      let normal = vector!(0,0,-1);
      let light = Light::point_light(point!(0,10,-10), rgb!(1,1,1));
-    let in_shadow = false;
-    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, in_shadow);
+    let light_transmittance = Color::WHITE;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &light_transmittance);
      assert_eq!(result, rgb!(1.6364, 1.6364, 1.6364));
  }
 
@@ -135,8 +211,8 @@ fn lighting_test_eye_offset_between_light_and_surface() {
      let eye = vector!(0,0,-1);
      let normal = vector!(0,0,-1);
      let light = Light::point_light(point!(0,0,10), rgb!(1,1,1));
-    let in_shadow = false;
-    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, in_shadow);
+    let light_transmittance = Color::WHITE;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &light_transmittance);
      assert_eq!(result, rgb!(0.1,0.1,0.1));
  }
 
@@ -148,8 +224,8 @@ fn lighting_test_eye_offset_between_light_and_surface() {
     let eye = vector!(0,0,-1);
     let normal = vector!(0,0,-1);
     let light = Light::point_light(point!(0,0,-10), rgb!(1,1,1));
-    let in_shadow = true;
-    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, in_shadow);
+    let light_transmittance = Color::BLACK;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &light_transmittance);
     assert_eq!(result, rgb!(0.1,0.1,0.1));
  }
 
@@ -164,9 +240,57 @@ fn lighting_test_eye_offset_between_light_and_surface() {
     let eye = vector!(0,0,-1);
     let normal = vector!(0,0,-1);
     let light = Light::point_light(point!(0,0,-10), rgb!(1,1,1));
-    let in_shadow = false;
-    let c1 = lighting(&material, &sphere, &light, &point!(0.9,0,0), &eye, &normal, in_shadow);
-    let c2 = lighting(&material, &sphere, &light, &point!(1.1,0,0), &eye, &normal, in_shadow);
+    let light_transmittance = Color::WHITE;
+    let c1 = lighting(&material, &sphere, &light, &point!(0.9,0,0), &eye, &normal, &light_transmittance);
+    let c2 = lighting(&material, &sphere, &light, &point!(1.1,0,0), &eye, &normal, &light_transmittance);
     assert_eq!(c1, Color::WHITE);
     assert_eq!(c2, Color::BLACK);
- }
\ No newline at end of file
+ }
+
+#[test]
+fn lighting_test_microfacet() {
+    let sphere = Shape::sphere();
+    let position = point!(0,0,0);
+    let eye = vector!(0,0,-1);
+    let normal = vector!(0,0,-1);
+    let light = Light::point_light(point!(0,0,-10), rgb!(1,1,1));
+
+    // a fully rough white dielectric lit head on reflects almost
+    // everything, 96% diffuse and the rest specular
+    let mut material = Material::microfacet(Color::WHITE, 1.0, 0.0);
+    material.ambient = 0.0;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &Color::WHITE);
+    assert_eq!(result, rgb!(0.97,0.97,0.97));
+
+    // a rough metal has no diffuse part and tints its reflection
+    let mut material = Material::microfacet(rgb!(1,0.5,0.25), 1.0, 1.0);
+    material.ambient = 0.0;
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &Color::WHITE);
+    assert_eq!(result, rgb!(0.25,0.125,0.0625));
+
+    // in shadow only ambient is left
+    let material = Material::microfacet(Color::WHITE, 0.5, 0.0);
+    let result = lighting(&material, &sphere, &light, &position, &eye, &normal, &Color::BLACK);
+    assert_eq!(result, rgb!(0.1,0.1,0.1));
+}
+
+#[test]
+fn lighting_test_microfacet_roughness() {
+    // smoother surfaces concentrate the highlight in the mirror direction
+    let sphere = Shape::sphere();
+    let position = point!(0,0,0);
+    let normal = vector!(0,0,-1);
+    let light = Light::point_light(point!(0,10,-10), rgb!(1,1,1));
+    let frac = consts::FRAC_1_SQRT_2;
+    let mirror = vector!(0,-frac,-frac);
+    let away = vector!(0,frac,-frac);
+    let smooth = Material::microfacet(Color::WHITE, 0.1, 1.0);
+    let rough = Material::microfacet(Color::WHITE, 0.8, 1.0);
+
+    let smooth_mirror = lighting(&smooth, &sphere, &light, &position, &mirror, &normal, &Color::WHITE);
+    let rough_mirror = lighting(&rough, &sphere, &light, &position, &mirror, &normal, &Color::WHITE);
+    let smooth_away = lighting(&smooth, &sphere, &light, &position, &away, &normal, &Color::WHITE);
+    let rough_away = lighting(&rough, &sphere, &light, &position, &away, &normal, &Color::WHITE);
+    assert!(smooth_mirror.red > rough_mirror.red);
+    assert!(smooth_away.red < rough_away.red);
+}
@@ -2,10 +2,10 @@
 #[macro_export]
 macro_rules! rgb {
     ($red:expr, $green:expr, $blue:expr) => {
-        crate::color::Color {
-            red: $red as f32,
-            green: $green as f32,
-            blue: $blue as f32
+        $crate::color::Color {
+            red: $red as $crate::math::Float,
+            green: $green as $crate::math::Float,
+            blue: $blue as $crate::math::Float
         }
     };
 }
@@ -13,11 +13,11 @@ macro_rules! rgb {
 #[macro_export]
 macro_rules! tuple {
     ($x:expr, $y:expr, $z:expr, $w:expr ) => {
-        crate::tuple::Tuple {
-            x: $x as f32,
-            y: $y as f32,
-            z: $z as f32,
-            w: $w as f32
+        $crate::tuple::Tuple {
+            x: $x as $crate::math::Float,
+            y: $y as $crate::math::Float,
+            z: $z as $crate::math::Float,
+            w: $w as $crate::math::Float
         }
     };
 }
@@ -25,10 +25,10 @@ macro_rules! tuple {
 #[macro_export]
 macro_rules! point {
     ($x:expr, $y:expr, $z:expr ) => {
-        crate::tuple::Point {
-            x: $x as f32,
-            y: $y as f32,
-            z: $z as f32,
+        $crate::tuple::Point {
+            x: $x as $crate::math::Float,
+            y: $y as $crate::math::Float,
+            z: $z as $crate::math::Float,
             w: 1.0
         }
     };
@@ -37,10 +37,10 @@ macro_rules! point {
 #[macro_export]
 macro_rules! vector {
     ($x:expr, $y:expr, $z:expr ) => {
-        crate::tuple::Vector {
-            x: $x as f32,
-            y: $y as f32,
-            z: $z as f32,
+        $crate::tuple::Vector {
+            x: $x as $crate::math::Float,
+            y: $y as $crate::math::Float,
+            z: $z as $crate::math::Float,
             w: 0.0
         }
     };
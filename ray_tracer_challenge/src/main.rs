@@ -1,23 +1,33 @@
-mod math;
-mod macro_def;
-mod tuple;
-mod color;
-mod canvas;
-mod matrix;
-mod ray;
-mod shape;
-mod light;
-mod material;
-mod world;
-mod camera;
-
-use camera::Camera;
-use world::World;
-use canvas::Canvas;
-use light::Light;
-use shape::Shape;
-use matrix::Matrix4x4;
-use material::Pattern;
+use ray_tracer_challenge::aov;
+use ray_tracer_challenge::canvas;
+use ray_tracer_challenge::math;
+use ray_tracer_challenge::matrix;
+use ray_tracer_challenge::render;
+use ray_tracer_challenge::stats;
+use ray_tracer_challenge::Camera;
+use ray_tracer_challenge::World;
+use ray_tracer_challenge::Canvas;
+use ray_tracer_challenge::Light;
+use ray_tracer_challenge::Shape;
+use ray_tracer_challenge::Matrix4x4;
+use ray_tracer_challenge::Pattern;
+use ray_tracer_challenge::math::consts;
+use ray_tracer_challenge::render::RenderSettings;
+use ray_tracer_challenge::render::Integrator;
+use ray_tracer_challenge::render::Region;
+use ray_tracer_challenge::render::AdaptiveSampling;
+use ray_tracer_challenge::occlusion::AmbientOcclusion;
+use ray_tracer_challenge::animation::Animation;
+use ray_tracer_challenge::animation::Interpolation;
+use ray_tracer_challenge::animation::View;
+use ray_tracer_challenge::progressive::ProgressiveRender;
+use ray_tracer_challenge::progressive::CancelToken;
+use ray_tracer_challenge::denoise::Denoiser;
+use ray_tracer_challenge::tonemap::ToneMap;
+use ray_tracer_challenge::tonemap::ToneMapping;
+use ray_tracer_challenge::point;
+use ray_tracer_challenge::vector;
+use ray_tracer_challenge::rgb;
 
 /*
 view ppm files
@@ -30,15 +40,181 @@ fn main() {
     let dimx = 500;
     let dimy = 250;
     let mut canvas = Canvas::new(dimx, dimy);
-    let mut camera = Camera::new(dimx, dimy, std::f32::consts::PI / 3.0);
+    let mut camera = Camera::new(dimx, dimy, consts::PI / 3.0);
     camera.transform = Matrix4x4::view_transform(&point!(0,1.5,-5), &point!(0,1,0), &point!(0,1,0));
 
     let world = create_world();
 
-    render(&camera, &world, &mut canvas);
+    // pass --path-traced to trade the phong shading for global illumination
+    // or --ambient-occlusion for a grey image of just the occlusion
+    let mut settings = if std::env::args().any(|arg| arg == "--path-traced") {
+        RenderSettings::path_traced(64, 8)
+    }
+    else if std::env::args().any(|arg| arg == "--ambient-occlusion") {
+        let mut settings = RenderSettings::new();
+        settings.integrator = Integrator::AmbientOcclusion(AmbientOcclusion::new(64, 2.0));
+        settings
+    }
+    else {
+        RenderSettings::new()
+    };
+
+    // --adaptive 256 keeps sampling noisy pixels, up to 256 samples each
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--adaptive") {
+        let max_samples = args.get(index + 1)
+            .and_then(|samples| samples.parse().ok())
+            .expect("--adaptive needs a number of samples");
+        settings.samples_per_pixel = settings.samples_per_pixel.max(4);
+        settings.adaptive = Some(AdaptiveSampling::new(max_samples, 0.01));
+    }
+
+    // --frames 1-48 writes a turntable as frame_0001.ppm to frame_0048.ppm
+    if let Some(index) = args.iter().position(|arg| arg == "--frames") {
+        let (first, last) = args.get(index + 1)
+            .and_then(|range| parse_frame_range(range))
+            .expect("--frames needs a range like 1-48");
+        let mut world = world;
+        turntable(48).render_frames(&mut camera, &mut world, &settings, first, last, std::path::Path::new("."))
+            .expect("unable to write frames");
+        return;
+    }
+
+    // counts only what the render below does
+    let ((), stats) = stats::collect(|| {
+        // --progressive 30 refines the image for 30 seconds, picking up from
+        // test.checkpoint if an earlier run of the same size and settings left
+        // one and saving it again after
+        if let Some(index) = args.iter().position(|arg| arg == "--progressive") {
+            let seconds: u64 = args.get(index + 1)
+                .and_then(|seconds| seconds.parse().ok())
+                .expect("--progressive needs a number of seconds");
+            let checkpoint = std::path::Path::new("test.checkpoint");
+            let mut progressive = match ProgressiveRender::load(checkpoint) {
+                Ok(progressive) if progressive.resumes(&camera, &settings) => progressive,
+                Ok(_) => {
+                    eprintln!("test.checkpoint is from another size or settings, starting over");
+                    ProgressiveRender::new(dimx, dimy, &settings)
+                },
+                Err(_) => ProgressiveRender::new(dimx, dimy, &settings)
+            };
+            let budget = std::time::Duration::from_secs(seconds);
+            progressive.run(&camera, &world, &settings, u32::MAX, Some(budget), &CancelToken::new());
+            progressive.save(checkpoint).expect("unable to write checkpoint");
+            canvas = progressive.to_canvas();
+        }
+        // --crop 200,100,50,40 renders just that part of the frame, x, y, width, height
+        else if let Some(index) = args.iter().position(|arg| arg == "--crop") {
+            let region = args.get(index + 1)
+                .and_then(|region| parse_region(region))
+                .expect("--crop needs a region like 200,100,50,40");
+            canvas = render::render_crop(&camera, &world, &settings, &region);
+        }
+        // --denoise smooths the noise of few samples using the normals, depths and albedos,
+        // --aovs writes those along with the object ids to depth.ppm, normal.ppm,
+        // albedo.ppm and object_id.ppm
+        else if args.iter().any(|arg| arg == "--denoise" || arg == "--aovs") {
+            let aovs = aov::render_with_aovs(&camera, &world, &settings, &mut canvas);
+            if args.iter().any(|arg| arg == "--aovs") {
+                let viewable = aovs.viewable();
+                std::fs::write("depth.ppm", canvas::to_ppm(&viewable.depth)).expect("unable to write file");
+                std::fs::write("normal.ppm", canvas::to_ppm(&viewable.normal)).expect("unable to write file");
+                std::fs::write("albedo.ppm", canvas::to_ppm(&viewable.albedo)).expect("unable to write file");
+                std::fs::write("object_id.ppm", canvas::to_ppm(&viewable.object_id)).expect("unable to write file");
+            }
+            if args.iter().any(|arg| arg == "--denoise") {
+                canvas = Denoiser::new().denoise(&canvas, &aovs);
+            }
+        }
+        // --heatmap shows how many samples each pixel took in samples.ppm
+        else if args.iter().any(|arg| arg == "--heatmap") {
+            let heatmap = render::render_with_heatmap(&camera, &world, &settings, &mut canvas);
+            std::fs::write("samples.ppm", canvas::to_ppm(&heatmap)).expect("unable to write file");
+        }
+        else {
+            render::render(&camera, &world, &settings, &mut canvas);
+        }
+    });
+
+    // --hdr also keeps the unclamped colors in test.pfm and test.hdr
+    if args.iter().any(|arg| arg == "--hdr") {
+        std::fs::write("test.pfm", canvas::to_pfm(&canvas)).expect("unable to write file");
+        std::fs::write("test.hdr", canvas::to_rgbe(&canvas)).expect("unable to write file");
+    }
+
+    // --bloom lets light brighter than white glow onto its surroundings
+    if args.iter().any(|arg| arg == "--bloom") {
+        canvas = canvas.bloom(1.0, dimy as math::Float / 100.0, 0.5);
+    }
+
+    // the 8 bit images are sRGB encoded, clamped unless --tonemap reinhard or
+    // aces says otherwise, --exposure 1.5 brightens them by that many stops
+    let operator = match args.iter().position(|arg| arg == "--tonemap").and_then(|index| args.get(index + 1)) {
+        Some(name) if name == "reinhard" => Some(ToneMap::Reinhard),
+        Some(name) if name == "aces" => Some(ToneMap::Aces),
+        Some(name) if name == "clamp" => Some(ToneMap::Clamp),
+        Some(name) => panic!("unknown tone map {}, try clamp, reinhard or aces", name),
+        None => None
+    };
+    let exposure = args.iter().position(|arg| arg == "--exposure")
+        .map(|index| args.get(index + 1).and_then(|stops| stops.parse().ok()).expect("--exposure needs a number of stops"));
+    let mut mapping = ToneMapping::new(operator.unwrap_or(ToneMap::Clamp));
+    mapping.exposure = exposure.unwrap_or(0.0);
+    canvas = mapping.apply(&canvas);
+
+    // --png writes test.png as well
+    if args.iter().any(|arg| arg == "--png") {
+        std::fs::write("test.png", canvas::to_png(&canvas)).expect("unable to write file");
+    }
 
     let s = canvas::to_ppm(&canvas);
     std::fs::write("test.ppm", s).expect("unable to write file");
+
+    // --stats prints ray counts and timings, --stats-json stats.json writes them out too
+    if args.iter().any(|arg| arg == "--stats") {
+        eprint!("{}", stats.report());
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--stats-json") {
+        let path = args.get(index + 1).expect("--stats-json needs a file name");
+        std::fs::write(path, stats.to_json()).expect("unable to write file");
+    }
+}
+
+fn parse_frame_range(range: &str) -> Option<(u32, u32)> {
+    let mut parts = range.splitn(2, '-');
+    let first = parts.next()?.parse().ok()?;
+    let last = match parts.next() {
+        Some(last) => last.parse().ok()?,
+        None => first
+    };
+    if first > last {
+        return None;
+    }
+    Some((first, last))
+}
+
+fn parse_region(region: &str) -> Option<Region> {
+    let values: Vec<u32> = region.split(',').map(|value| value.parse().ok()).collect::<Option<_>>()?;
+    match values[..] {
+        [x, y, width, height] => Some(Region::new(x, y, width, height)),
+        _ => None
+    }
+}
+
+/*
+    the camera circling the scene once every frames frames at 24 per
+    second, with a key on every frame so each one is on the circle,
+    lerping between fewer keys would cut the corners
+*/
+fn turntable(frames: u32) -> Animation {
+    let mut animation = Animation::new(24.0);
+    for frame in 0..=frames {
+        let angle = frame as math::Float * 2.0 * consts::PI / frames as math::Float;
+        let from = point!(-5.0 * angle.sin(), 1.5, -5.0 * angle.cos());
+        let view = View { from, to: point!(0,1,0), up: vector!(0,1,0) };
+        animation.camera.add(animation.frame_time(frame), view, Interpolation::Linear);
+    }
+    animation
 }
 
 fn create_world() -> World {
@@ -52,17 +228,17 @@ fn create_world() -> World {
     world.objects.push(plane);
 
     let mut plane = Shape::plane();
-    plane.material.pattern = Pattern::checkered(rgb!(1,0.5,0.5), rgb!(0.5,1,0.5), matrix::MATRIX_4X4_IDENTITY);
+    plane.material.pattern = Pattern::planar_checkered(rgb!(1,0.5,0.5), rgb!(0.5,1,0.5), matrix::MATRIX_4X4_IDENTITY);
     plane.material.specular = 0.0;
-    plane.transform = Matrix4x4::rotation_x(std::f32::consts::PI / 2.0)
+    plane.transform = Matrix4x4::rotation_x(consts::PI / 2.0)
         .multiply(&Matrix4x4::translation(0.0,10.0,0.0));
     world.objects.push(plane);
 
     let mut sphere = Shape::sphere();
     sphere.transform = Matrix4x4::translation(-0.5, 1.0, 0.5);
     let pattern_transform = Matrix4x4::scaling(0.1,0.1,0.1)
-        .multiply(&Matrix4x4::rotation_z(std::f32::consts::PI / 4.0))
-        .multiply(&Matrix4x4::rotation_y(std::f32::consts::PI / 4.0));
+        .multiply(&Matrix4x4::rotation_z(consts::PI / 4.0))
+        .multiply(&Matrix4x4::rotation_y(consts::PI / 4.0));
     sphere.material.pattern = Pattern::stripe(rgb!(0.1,0.1,1), rgb!(1,1,1), pattern_transform);
     sphere.material.color = rgb!(0.1,0.1,1);
     sphere.material.diffuse = 0.7;
@@ -79,12 +255,17 @@ fn create_world() -> World {
     return world;
 }
 
-fn render(camera: &Camera, world: &World, canvas: &mut Canvas) {
-    for y in 0..camera.vsize {
-        for x in 0..camera.hsize {
-            let ray = camera.ray_for_pixel(x, y);
-            let color = world.color_at(&ray);
-            canvas.set_pixel(x, y, &color);
-        }
+#[test]
+fn frames_test() {
+    assert_eq!(parse_frame_range("1-48"), Some((1, 48)));
+    assert_eq!(parse_frame_range("7"), Some((7, 7)));
+    assert_eq!(parse_frame_range("48-1"), None);
+    assert_eq!(parse_frame_range("1-"), None);
+
+    // every frame is the same distance from the turntable's axis
+    let animation = turntable(48);
+    for frame in 0..=48 {
+        let from = animation.camera.value_at(animation.frame_time(frame)).unwrap().from;
+        assert!(math::fequal((from.x * from.x + from.z * from.z).sqrt(), 5.0));
     }
-}
\ No newline at end of file
+}
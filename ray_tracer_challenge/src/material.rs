@@ -1,18 +1,52 @@
 use crate::color::Color;
+use crate::math::Float;
 use crate::tuple::Point;
+use crate::tuple::Vector;
+use crate::math;
 use crate::rgb;
 use crate::point;
+use crate::vector;
+use crate::noise;
+use crate::math::consts;
+use crate::texture::Texture;
+use crate::media::Volume;
 use crate::matrix;
 use crate::shape::Shape;
 use crate::matrix::Matrix4x4;
 
 pub struct Material {
     pub color: Color,
-    pub ambient: f32,
-    pub diffuse: f32,
-    pub specular: f32,
-    pub shininess: f32,
-    pub pattern: Option<Pattern>
+    pub ambient: Float,
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+    pub pattern: Option<Pattern>,
+    // small scale bumps applied to the shading normal only
+    pub normal_perturbation: Option<NormalPerturbation>,
+    // how light::lighting turns the above into reflected light
+    pub shading: ShadingModel,
+    // 0 is opaque, 1 lets all light through tinted by the color
+    pub transparency: Float,
+    // false leaves the object out of shadow tests altogether
+    pub casts_shadow: bool,
+    /*
+        fills the shape with a participating medium instead of
+        drawing its surface, the shape has to be closed
+    */
+    pub volume: Option<Volume>
+}
+
+/*
+    Phong uses diffuse, specular and shininess
+
+    Microfacet is a physically based model, the color (or pattern)
+    is the base color, roughness runs from mirror smooth at 0 to
+    fully matte at 1 and metallic from dielectric at 0 to metal at 1,
+    diffuse, specular and shininess are ignored
+*/
+pub enum ShadingModel {
+    Phong,
+    Microfacet { roughness: Float, metallic: Float }
 }
 
 impl Material {
@@ -23,91 +57,406 @@ impl Material {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
-            pattern: None
+            pattern: None,
+            normal_perturbation: None,
+            shading: ShadingModel::Phong,
+            transparency: 0.0,
+            casts_shadow: true,
+            volume: None
+        }
+    }
+
+    // a microfacet material, ambient keeps its phong default
+    pub fn microfacet(base_color: Color, roughness: Float, metallic: Float) -> Material {
+        let mut material = Material::new();
+        material.color = base_color;
+        material.shading = ShadingModel::Microfacet {
+            roughness: roughness.clamp(0.0, 1.0),
+            metallic: metallic.clamp(0.0, 1.0)
+        };
+        material
+    }
+
+    // the surface color before any lighting, from the pattern if there is one
+    pub fn color_at(&self, shape: &Shape, world_point: &Point) -> Color {
+        self.color_at_time(shape, world_point, 0.0)
+    }
+
+    // the pattern follows a moving shape, so it is looked up in its pose at time
+    pub fn color_at_time(&self, shape: &Shape, world_point: &Point, time: Float) -> Color {
+        match &self.pattern {
+            Some(pattern) => pattern.color_at_time(shape, world_point, time),
+            None => self.color.clone()
         }
     }
+
+    // the fraction of light scattered diffusely, metals have none
+    pub fn diffuse_albedo(&self, shape: &Shape, world_point: &Point, time: Float) -> Color {
+        let color = self.color_at_time(shape, world_point, time);
+        match self.shading {
+            ShadingModel::Phong => color.multiplyf(self.diffuse),
+            ShadingModel::Microfacet { metallic, .. } => color.multiplyf(1.0 - metallic)
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new()
+    }
+}
+
+pub enum NormalPerturbation {
+    /*
+        bumps from fractal noise in object space, scale is the
+        noise frequency and amount how strongly the normal tilts
+    */
+    Noise { scale: Float, amount: Float, octaves: u32 },
+    /*
+        tangent space normal map, each texel's rgb holds a normal
+        with 0.5, 0.5, 1 being flat, the tangent follows increasing
+        u of the texture's mapping and the bitangent increasing v
+    */
+    NormalMap(Texture)
+}
+
+impl NormalPerturbation {
+    // time picks the pose of a moving shape, as for Shape::normal_at_time
+    pub fn perturb(&self, shape: &Shape, world_point: &Point, normal: &Vector, time: Float) -> Vector {
+        let inverse = shape.transform_at(time).inverse();
+        let local_point = inverse.multiply_tuple(world_point);
+        match self {
+            NormalPerturbation::Noise { scale, amount, octaves } => {
+                // the bump height is the noise, tilt the normal against its slope
+                let height = |p: &Point| noise::fractal(&p.multiplyf(*scale), *octaves);
+                let mut slope = inverse.transpose().multiply_tuple(&gradient(&local_point, height));
+                slope.w = 0.0;
+                let along_surface = slope.subtract(&normal.multiplyf(slope.dot(normal)));
+                normal.subtract(&along_surface.multiplyf(*amount)).normalize()
+            },
+            NormalPerturbation::NormalMap(texture) => {
+                let (u, v) = texture.mapping.map(&local_point);
+                let texel = texture.sample(u, v);
+                let tangent_normal = vector!(texel.red * 2.0 - 1.0, texel.green * 2.0 - 1.0, texel.blue * 2.0 - 1.0);
+
+                // directions of increasing u and v on the surface, as gradients
+                // they go to world space like normals do
+                let wrapped = |d: Float| d - d.round();
+                let du = gradient(&local_point, |p: &Point| wrapped(texture.mapping.map(p).0 - u));
+                let dv = gradient(&local_point, |p: &Point| wrapped(texture.mapping.map(p).1 - v));
+                let to_world = inverse.transpose();
+                let tangent = surface_direction(&to_world.multiply_tuple(&du), normal);
+                let bitangent = surface_direction(&to_world.multiply_tuple(&dv), normal);
+
+                tangent.multiplyf(tangent_normal.x)
+                    .add(&bitangent.multiplyf(tangent_normal.y))
+                    .add(&normal.multiplyf(tangent_normal.z))
+                    .normalize()
+            }
+        }
+    }
+}
+
+// central difference gradient of f around point
+fn gradient(point: &Point, f: impl Fn(&Point) -> Float) -> Vector {
+    let h = 0.001;
+    let dx = vector!(h, 0, 0);
+    let dy = vector!(0, h, 0);
+    let dz = vector!(0, 0, h);
+    vector!(
+        (f(&point.add(&dx)) - f(&point.subtract(&dx))) / (2.0 * h),
+        (f(&point.add(&dy)) - f(&point.subtract(&dy))) / (2.0 * h),
+        (f(&point.add(&dz)) - f(&point.subtract(&dz))) / (2.0 * h)
+    )
+}
+
+// v flattened onto the plane of normal, zero if v is parallel to it
+fn surface_direction(v: &Vector, normal: &Vector) -> Vector {
+    let mut v = v.clone();
+    v.w = 0.0;
+    let flat = v.subtract(&normal.multiplyf(v.dot(normal)));
+    if flat.magnitude() < math::EPSILON {
+        vector!(0,0,0)
+    }
+    else {
+        flat.normalize()
+    }
 }
 
 pub enum PatternType {
     Stripe,
     Gradient,
-    Checkered
+    Checkered,
+    PlanarCheckered,
+    Ring,
+    RadialGradient,
+    Marble,
+    Wood,
+    Turbulence,
+    BlendAverage,
+    BlendMultiply,
+    Texture(Texture)
+}
+
+// octaves used by the noise driven patterns
+const NOISE_OCTAVES : u32 = 4;
+
+/*
+    offsets the pattern point by fractal noise before the
+    pattern is evaluated, turning straight edges wavy
+*/
+pub struct Jitter {
+    pub scale: Float,
+    pub octaves: u32
+}
+
+impl Jitter {
+    fn perturb(&self, point: &Point) -> Point {
+        // sample the noise at three far apart places so each axis moves independently
+        let dx = noise::fractal(point, self.octaves);
+        let dy = noise::fractal(&point.add(&vector!(31.4, 17.9, 5.3)), self.octaves);
+        let dz = noise::fractal(&point.add(&vector!(-11.2, 43.7, 23.1)), self.octaves);
+        point.add(&vector!(dx, dy, dz).multiplyf(self.scale))
+    }
+}
+
+/*
+    each half of a pattern is either a flat color or another
+    pattern, sub patterns are evaluated in the parent's pattern
+    space and then apply their own transform on top
+*/
+pub enum PatternSlot {
+    Color(Color),
+    Pattern(Box<Pattern>)
+}
+
+impl PatternSlot {
+    fn color_at(&self, point: &Point) -> Color {
+        match self {
+            PatternSlot::Color(color) => color.clone(),
+            PatternSlot::Pattern(pattern) => pattern.local_color_at(point)
+        }
+    }
+}
+
+impl From<Color> for PatternSlot {
+    fn from(color: Color) -> PatternSlot {
+        PatternSlot::Color(color)
+    }
+}
+
+impl From<Pattern> for PatternSlot {
+    fn from(pattern: Pattern) -> PatternSlot {
+        PatternSlot::Pattern(Box::new(pattern))
+    }
 }
 
 pub struct Pattern {
     pattern_type: PatternType,
     pub transform: Matrix4x4,
-    pub color1: Color,
-    pub color2: Color
+    pub color1: PatternSlot,
+    pub color2: PatternSlot,
+    pub jitter: Option<Jitter>
 }
 
 impl Pattern {
-    pub fn stripe(color1: Color, color2: Color, transform: Matrix4x4) -> Option<Pattern> {
+    fn new(pattern_type: PatternType, color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
         Some(Pattern {
-            pattern_type: PatternType::Stripe,
+            pattern_type,
             transform,
-            color1,
-            color2
+            color1: color1.into(),
+            color2: color2.into(),
+            jitter: None
         })
     }
 
-    pub fn gradient(color1: Color, color2: Color, transform: Matrix4x4) -> Option<Pattern> {
-        Some(Pattern {
-            pattern_type: PatternType::Gradient,
-            transform,
-            color1,
-            color2
-        })
+    pub fn stripe(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Stripe, color1, color2, transform)
     }
 
-    pub fn checkered(color1: Color, color2: Color, transform: Matrix4x4) -> Option<Pattern> {
-        Some(Pattern {
-            pattern_type: PatternType::Checkered,
-            transform,
-            color1,
-            color2
+    pub fn gradient(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Gradient, color1, color2, transform)
+    }
+
+    pub fn checkered(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Checkered, color1, color2, transform)
+    }
+
+    // squares alternating in x and z only, for planes lying in xz
+    pub fn planar_checkered(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::PlanarCheckered, color1, color2, transform)
+    }
+
+    // concentric rings around the y axis, one unit wide
+    pub fn ring(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Ring, color1, color2, transform)
+    }
+
+    // blends outward from the y axis, repeating every unit of distance
+    pub fn radial_gradient(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::RadialGradient, color1, color2, transform)
+    }
+
+    // veins of color2 running through color1 along x
+    pub fn marble(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Marble, color1, color2, transform)
+    }
+
+    // noisy concentric growth rings around the y axis
+    pub fn wood(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Wood, color1, color2, transform)
+    }
+
+    // blends from color1 to color2 by the turbulence at the point
+    pub fn turbulence(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Turbulence, color1, color2, transform)
+    }
+
+    // the mean of both slots, e.g. two stripe patterns at right angles make a plaid
+    pub fn blend_average(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::BlendAverage, color1, color2, transform)
+    }
+
+    // both slots multiplied together, handy for darkening one pattern with another
+    pub fn blend_multiply(color1: impl Into<PatternSlot>, color2: impl Into<PatternSlot>, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::BlendMultiply, color1, color2, transform)
+    }
+
+    // an image wrapped onto the shape, the color slots are unused
+    pub fn texture(texture: Texture, transform: Matrix4x4) -> Option<Pattern> {
+        Pattern::new(PatternType::Texture(texture), Color::BLACK, Color::BLACK, transform)
+    }
+
+    /*
+        wraps a pattern so its point is perturbed by noise before lookup,
+        scale is how far in pattern space the point can move
+    */
+    pub fn jitter(pattern: Option<Pattern>, scale: Float, octaves: u32) -> Option<Pattern> {
+        pattern.map(|mut p| {
+            p.jitter = Some(Jitter { scale, octaves });
+            p
         })
     }
 
     pub fn color_at(&self, shape: &Shape, world_point: &Point) -> Color {
-        let shape_point = shape.transform.inverse().multiply_tuple(&world_point);
-        let pattern_point = self.transform.inverse().multiply_tuple(&shape_point);
-        match self.pattern_type {
-            PatternType::Stripe => stripe_color_at(&self, &pattern_point),
-            PatternType::Gradient => gradient_color_at(&self, &pattern_point),
-            PatternType::Checkered => checkered_color_at(&self, &pattern_point)
+        self.color_at_time(shape, world_point, 0.0)
+    }
+
+    pub fn color_at_time(&self, shape: &Shape, world_point: &Point, time: Float) -> Color {
+        let shape_point = shape.transform_at(time).inverse().multiply_tuple(world_point);
+        self.local_color_at(&shape_point)
+    }
+
+    fn local_color_at(&self, shape_point: &Point) -> Color {
+        let mut pattern_point = self.transform.inverse().multiply_tuple(shape_point);
+        if let Some(jitter) = &self.jitter {
+            pattern_point = jitter.perturb(&pattern_point);
+        }
+        match &self.pattern_type {
+            PatternType::Stripe => stripe_color_at(self, &pattern_point),
+            PatternType::Gradient => gradient_color_at(self, &pattern_point),
+            PatternType::Checkered => checkered_color_at(self, &pattern_point),
+            PatternType::PlanarCheckered => planar_checkered_color_at(self, &pattern_point),
+            PatternType::Ring => ring_color_at(self, &pattern_point),
+            PatternType::RadialGradient => radial_gradient_color_at(self, &pattern_point),
+            PatternType::Marble => marble_color_at(self, &pattern_point),
+            PatternType::Wood => wood_color_at(self, &pattern_point),
+            PatternType::Turbulence => turbulence_color_at(self, &pattern_point),
+            PatternType::BlendAverage => mix(self, &pattern_point, 0.5),
+            PatternType::BlendMultiply => {
+                self.color1.color_at(&pattern_point).hadamard(&self.color2.color_at(&pattern_point))
+            },
+            PatternType::Texture(texture) => texture.color_at(&pattern_point)
         }
     }
 }
 
+// blend between the two slots of the pattern at point
+fn mix(pattern: &Pattern, point: &Point, fraction: Float) -> Color {
+    let a = pattern.color1.color_at(point);
+    let b = pattern.color2.color_at(point);
+    a.add(&b.subtract(&a).multiplyf(fraction))
+}
+
 fn stripe_color_at(pattern: &Pattern, point: &Point) -> Color {
     if (point.x.floor() as i32) % 2 == 0 {
-        pattern.color1.clone()
+        pattern.color1.color_at(point)
     }
     else {
-        pattern.color2.clone()
+        pattern.color2.color_at(point)
     }
 }
 
 fn gradient_color_at(pattern: &Pattern, point: &Point) -> Color {
-    let color_distance = pattern.color2.subtract(&pattern.color1);
+    let color1 = pattern.color1.color_at(point);
+    let color_distance = pattern.color2.color_at(point).subtract(&color1);
     let fraction = point.x - point.x.floor();
-    pattern.color1.add(&color_distance.multiplyf(fraction))
+    color1.add(&color_distance.multiplyf(fraction))
 }
 
 fn checkered_color_at(pattern: &Pattern, point: &Point) -> Color {
     let floorx = point.x.floor() as i32;
-    //let floory = point.y.floor() as i32;
+    let floory = point.y.floor() as i32;
+    let floorz = point.z.floor() as i32;
+    let mod2 = (floorx + floory + floorz) % 2;
+    if mod2 == 0 {
+        pattern.color1.color_at(point)
+    }
+    else {
+        pattern.color2.color_at(point)
+    }
+}
+
+/*
+    ignores y so a plane sitting at y = 0 doesn't pick up
+    noise from points landing just above or below the surface
+*/
+fn planar_checkered_color_at(pattern: &Pattern, point: &Point) -> Color {
+    let floorx = point.x.floor() as i32;
     let floorz = point.z.floor() as i32;
     let mod2 = (floorx + floorz) % 2;
     if mod2 == 0 {
-        pattern.color1.clone()
+        pattern.color1.color_at(point)
+    }
+    else {
+        pattern.color2.color_at(point)
+    }
+}
+
+fn ring_color_at(pattern: &Pattern, point: &Point) -> Color {
+    let distance = (point.x * point.x + point.z * point.z).sqrt();
+    if (distance.floor() as i32) % 2 == 0 {
+        pattern.color1.color_at(point)
     }
     else {
-        pattern.color2.clone()
+        pattern.color2.color_at(point)
     }
 }
 
+fn radial_gradient_color_at(pattern: &Pattern, point: &Point) -> Color {
+    let distance = (point.x * point.x + point.z * point.z).sqrt();
+    let fraction = distance - distance.floor();
+    mix(pattern, point, fraction)
+}
+
+fn marble_color_at(pattern: &Pattern, point: &Point) -> Color {
+    let turbulence = noise::turbulence(point, NOISE_OCTAVES);
+    let vein = (consts::PI * (point.x + 4.0 * turbulence)).sin() * 0.5 + 0.5;
+    mix(pattern, point, vein)
+}
+
+fn wood_color_at(pattern: &Pattern, point: &Point) -> Color {
+    let radius = (point.x * point.x + point.z * point.z).sqrt();
+    let ring = radius + 0.5 * noise::fractal(point, NOISE_OCTAVES);
+    let grain = ring - ring.floor();
+    mix(pattern, point, grain)
+}
+
+fn turbulence_color_at(pattern: &Pattern, point: &Point) -> Color {
+    let fraction = noise::turbulence(point, NOISE_OCTAVES).min(1.0);
+    mix(pattern, point, fraction)
+}
+
 #[test]
 fn stripe_color_at_test() {
     let pattern = Pattern::stripe(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
@@ -144,6 +493,46 @@ fn checkered_color_at_test() {
     assert_eq!(Color::BLACK, checkered_color_at(&pattern, &point!(0,0,1.01)));
 }
 
+#[test]
+fn planar_checkered_color_at_test() {
+    let pattern = Pattern::planar_checkered(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    assert_eq!(Color::WHITE, planar_checkered_color_at(&pattern, &point!(0,0,0)));
+    assert_eq!(Color::WHITE, planar_checkered_color_at(&pattern, &point!(0.99,0,0)));
+    assert_eq!(Color::BLACK, planar_checkered_color_at(&pattern, &point!(1.01,0,0)));
+
+    // y is ignored
+    assert_eq!(Color::WHITE, planar_checkered_color_at(&pattern, &point!(0,1.01,0)));
+    assert_eq!(Color::WHITE, planar_checkered_color_at(&pattern, &point!(0,-0.0001,0)));
+
+    assert_eq!(Color::WHITE, planar_checkered_color_at(&pattern, &point!(0,0,0.99)));
+    assert_eq!(Color::BLACK, planar_checkered_color_at(&pattern, &point!(0,0,1.01)));
+    assert_eq!(Color::WHITE, planar_checkered_color_at(&pattern, &point!(1.01,0,1.01)));
+    assert_eq!(Color::BLACK, planar_checkered_color_at(&pattern, &point!(-0.5,0,0.5)));
+}
+
+#[test]
+fn ring_color_at_test() {
+    let pattern = Pattern::ring(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    assert_eq!(Color::WHITE, ring_color_at(&pattern, &point!(0,0,0)));
+    assert_eq!(Color::BLACK, ring_color_at(&pattern, &point!(1,0,0)));
+    assert_eq!(Color::BLACK, ring_color_at(&pattern, &point!(0,0,1)));
+    // 0.708 = just slightly more than sqrt(2)/2
+    assert_eq!(Color::BLACK, ring_color_at(&pattern, &point!(0.708,0,0.708)));
+    assert_eq!(Color::WHITE, ring_color_at(&pattern, &point!(-2.1,0,0)));
+    // y is ignored
+    assert_eq!(Color::WHITE, ring_color_at(&pattern, &point!(0,5,0)));
+}
+
+#[test]
+fn radial_gradient_color_at_test() {
+    let pattern = Pattern::radial_gradient(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    assert_eq!(Color::WHITE, radial_gradient_color_at(&pattern, &point!(0,0,0)));
+    assert_eq!(rgb!(0.75,0.75,0.75), radial_gradient_color_at(&pattern, &point!(0.25,0,0)));
+    assert_eq!(rgb!(0.5,0.5,0.5), radial_gradient_color_at(&pattern, &point!(0,0,-0.5)));
+    assert_eq!(rgb!(0.5,0.5,0.5), radial_gradient_color_at(&pattern, &point!(0.3,0,0.4)));
+    assert_eq!(rgb!(0.25,0.25,0.25), radial_gradient_color_at(&pattern, &point!(-0.75,3,0)));
+}
+
 #[test]
 fn pattern_color_at_test() {
     // stripes with object transformation
@@ -165,4 +554,177 @@ fn pattern_color_at_test() {
     let pattern = Pattern::stripe(Color::WHITE, Color::BLACK, Matrix4x4::translation(0.5, 0.0, 0.0)).unwrap();
     let c = pattern.color_at(&sphere, &point!(2.5, 0,0));
     assert_eq!(c, Color::WHITE);
-}
\ No newline at end of file
+
+    // stripes stay on a moving shape
+    let mut sphere = Shape::sphere();
+    sphere.set_end_transform(Some(Matrix4x4::translation(3.0, 0.0, 0.0)));
+    let pattern = Pattern::stripe(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    assert_eq!(pattern.color_at(&sphere, &point!(3.5, 0,0)), Color::BLACK);
+    assert_eq!(pattern.color_at_time(&sphere, &point!(3.5, 0,0), 1.0), Color::WHITE);
+    assert_eq!(pattern.color_at(&sphere, &point!(1.5, 0,0)), Color::BLACK);
+    assert_eq!(pattern.color_at_time(&sphere, &point!(1.5, 0,0), 0.5), Color::WHITE);
+}
+
+#[test]
+fn noise_patterns_test() {
+    // noise patterns only ever blend between their two colors
+    let patterns = [
+        Pattern::marble(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap(),
+        Pattern::wood(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap(),
+        Pattern::turbulence(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap()];
+    for pattern in patterns.iter() {
+        let mut shades = Vec::new();
+        for i in 0..50 {
+            let t = i as Float * 0.173;
+            let c = pattern.local_color_at(&point!(t, t * 0.5 - 1.0, 2.0 - t));
+            assert!(c.red >= 0.0 && c.red <= 1.0);
+            assert!(c.red == c.green && c.green == c.blue);
+            shades.push(c.red);
+        }
+        // and they are not flat
+        let first = shades[0];
+        assert!(shades.iter().any(|s| (s - first).abs() > 0.1));
+    }
+
+    // without noise wood is plain rings around y
+    let wood = Pattern::wood(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    assert_eq!(Color::WHITE, wood_color_at(&wood, &point!(0,0,0)));
+}
+
+#[test]
+fn jitter_test() {
+    // no scale means no perturbation
+    let pattern = Pattern::jitter(Pattern::stripe(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY), 0.0, 3).unwrap();
+    assert_eq!(Color::WHITE, pattern.local_color_at(&point!(0.9,0.3,0.2)));
+    assert_eq!(Color::BLACK, pattern.local_color_at(&point!(1.1,0.3,0.2)));
+
+    // with scale the stripe edge wanders away from x = 1
+    let pattern = Pattern::jitter(Pattern::stripe(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY), 0.5, 3).unwrap();
+    let moved = (0..100).any(|i| {
+        let p = point!(0.95, i as Float * 0.37, i as Float * 0.11);
+        pattern.local_color_at(&p) == Color::BLACK
+    });
+    assert!(moved);
+}
+
+#[test]
+fn nested_pattern_test() {
+    // stripes whose first stripe is itself checkered, scaling the
+    // stripes also scales the checks inside them
+    let checks = Pattern::checkered(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    let red = rgb!(1,0,0);
+    let pattern = Pattern::stripe(checks, red.clone(), Matrix4x4::scaling(2.0, 2.0, 2.0)).unwrap();
+    assert_eq!(Color::WHITE, pattern.local_color_at(&point!(0.5,0.5,0.5)));
+    assert_eq!(Color::BLACK, pattern.local_color_at(&point!(0.5,2.5,0.5)));
+    assert_eq!(Color::BLACK, pattern.local_color_at(&point!(0.5,0.5,2.5)));
+    assert_eq!(red, pattern.local_color_at(&point!(2.5,0.5,0.5)));
+
+    // the sub pattern transform is applied on top of the parent's
+    let checks = Pattern::checkered(Color::WHITE, Color::BLACK, Matrix4x4::translation(0.5, 0.0, 0.0)).unwrap();
+    let pattern = Pattern::stripe(checks, red.clone(), Matrix4x4::scaling(2.0, 2.0, 2.0)).unwrap();
+    assert_eq!(Color::BLACK, pattern.local_color_at(&point!(0.5,0.5,0.5)));
+    assert_eq!(Color::WHITE, pattern.local_color_at(&point!(1.5,0.5,0.5)));
+
+    // and the whole tree is evaluated through a shape
+    let mut sphere = Shape::sphere();
+    sphere.transform = Matrix4x4::scaling(2.0, 2.0, 2.0);
+    assert_eq!(red, pattern.color_at(&sphere, &point!(5,1,1)));
+}
+
+#[test]
+fn blend_pattern_test() {
+    let vertical = Pattern::stripe(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    let horizontal = Pattern::stripe(Color::WHITE, rgb!(0.5,0,0), Matrix4x4::rotation_y(consts::PI / 2.0)).unwrap();
+    let average = Pattern::blend_average(vertical, horizontal, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    assert_eq!(Color::WHITE, average.local_color_at(&point!(0.5,0,-0.5)));
+    assert_eq!(rgb!(0.5,0.5,0.5), average.local_color_at(&point!(1.5,0,-0.5)));
+    assert_eq!(rgb!(0.25,0,0), average.local_color_at(&point!(1.5,0,0.5)));
+
+    let multiply = Pattern::blend_multiply(rgb!(0.5,1,1), Pattern::gradient(Color::WHITE, Color::BLACK, matrix::MATRIX_4X4_IDENTITY).unwrap(), matrix::MATRIX_4X4_IDENTITY).unwrap();
+    assert_eq!(rgb!(0.5,1,1), multiply.local_color_at(&point!(0,0,0)));
+    assert_eq!(rgb!(0.25,0.5,0.5), multiply.local_color_at(&point!(0.5,0,0)));
+}
+
+#[test]
+fn texture_pattern_test() {
+    use crate::texture::{UvMapping, TextureFilter};
+    use crate::canvas::Canvas;
+
+    // a two pixel wide image, left half red and right half blue
+    let mut canvas = Canvas::new(2, 1);
+    canvas.set_pixel(0, 0, &rgb!(1,0,0));
+    canvas.set_pixel(1, 0, &rgb!(0,0,1));
+    let texture = Texture::new(canvas, UvMapping::Spherical, TextureFilter::Nearest);
+    let pattern = Pattern::texture(texture, matrix::MATRIX_4X4_IDENTITY).unwrap();
+    let sphere = Shape::sphere();
+    // u wraps around the sphere starting at -z
+    assert_eq!(rgb!(1,0,0), pattern.color_at(&sphere, &point!(0.2,0,-1)));
+    assert_eq!(rgb!(0,0,1), pattern.color_at(&sphere, &point!(-0.2,0,-1)));
+}
+
+#[test]
+fn noise_normal_perturbation_test() {
+    let plane = Shape::plane();
+    let normal = vector!(0,1,0);
+
+    // no amount leaves the normal alone
+    let flat = NormalPerturbation::Noise { scale: 4.0, amount: 0.0, octaves: 3 };
+    assert_eq!(normal, flat.perturb(&plane, &point!(0.3,0,0.7), &normal, 0.0));
+
+    let bumpy = NormalPerturbation::Noise { scale: 4.0, amount: 0.5, octaves: 3 };
+    let mut tilted = false;
+    for i in 0..20 {
+        let p = point!(i as Float * 0.31, 0, i as Float * 0.17);
+        let n = bumpy.perturb(&plane, &p, &normal, 0.0);
+        assert!(math::fequal(n.magnitude(), 1.0));
+        assert!(n.y > 0.0);
+        tilted = tilted || n != normal;
+    }
+    assert!(tilted);
+}
+
+#[test]
+fn normal_map_perturbation_test() {
+    use crate::canvas::Canvas;
+    use crate::texture::{UvMapping, TextureFilter};
+
+    let plane = Shape::plane();
+    let normal = vector!(0,1,0);
+    let mut canvas = Canvas::new(1, 1);
+
+    // the flat color keeps the geometric normal
+    canvas.set_pixel(0, 0, &rgb!(0.5,0.5,1));
+    let map = NormalPerturbation::NormalMap(Texture::new(canvas, UvMapping::Planar, TextureFilter::Nearest));
+    assert_eq!(normal, map.perturb(&plane, &point!(0.5,0,0.5), &normal, 0.0));
+
+    // planar u follows x and v follows z
+    let mut canvas = Canvas::new(1, 1);
+    canvas.set_pixel(0, 0, &rgb!(1,0.5,0.5));
+    let map = NormalPerturbation::NormalMap(Texture::new(canvas, UvMapping::Planar, TextureFilter::Nearest));
+    assert_eq!(vector!(1,0,0), map.perturb(&plane, &point!(0.5,0,0.5), &normal, 0.0));
+
+    let mut canvas = Canvas::new(1, 1);
+    canvas.set_pixel(0, 0, &rgb!(0.5,1,0.5));
+    let map = NormalPerturbation::NormalMap(Texture::new(canvas, UvMapping::Planar, TextureFilter::Nearest));
+    assert_eq!(vector!(0,0,1), map.perturb(&plane, &point!(0.5,0,0.5), &normal, 0.0));
+
+    // the tangent frame follows the shape's transform
+    let mut plane = Shape::plane();
+    plane.transform = Matrix4x4::rotation_z(consts::PI / 2.0);
+    let normal = plane.normal_at(&point!(0,0.5,0.5));
+    canvas = Canvas::new(1, 1);
+    canvas.set_pixel(0, 0, &rgb!(1,0.5,0.5));
+    let map = NormalPerturbation::NormalMap(Texture::new(canvas, UvMapping::Planar, TextureFilter::Nearest));
+    assert_eq!(vector!(0,1,0), map.perturb(&plane, &point!(0,0.5,0.5), &normal, 0.0));
+
+    // and stays at right angles to the lines of equal u under a non-uniform scale
+    let mut plane = Shape::plane();
+    plane.transform = Matrix4x4::scaling(1.0, 1.0, 3.0).multiply(&Matrix4x4::rotation_y(consts::PI / 4.0));
+    let world_point = plane.transform.multiply_tuple(&point!(0.5,0,0.5));
+    let normal = plane.normal_at(&world_point);
+    canvas = Canvas::new(1, 1);
+    canvas.set_pixel(0, 0, &rgb!(1,0.5,0.5));
+    let map = NormalPerturbation::NormalMap(Texture::new(canvas, UvMapping::Planar, TextureFilter::Nearest));
+    let sqrt_10 = (10.0 as Float).sqrt();
+    assert_eq!(vector!(3.0 / sqrt_10, 0, -1.0 / sqrt_10), map.perturb(&plane, &world_point, &normal, 0.0));
+}
@@ -1,7 +1,27 @@
+/*
+    the numeric type used by the whole tracer
 
-pub const EPSILON : f32 = 0.0001;
+    f32 is the default, build with --features f64 to render
+    large scenes where f32 runs out of precision
+*/
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
 
-pub fn fequal(a: f32, b: f32) -> bool {
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+// tolerance for comparisons and the offset used to push points
+// off of surfaces, f64 can afford a much tighter value
+#[cfg(not(feature = "f64"))]
+pub const EPSILON : Float = 0.0001;
+#[cfg(feature = "f64")]
+pub const EPSILON : Float = 0.00001;
+
+pub fn fequal(a: Float, b: Float) -> bool {
     let mut x = a - b;
     if x < 0.0 {
         x *= -1.0;
@@ -13,4 +33,3 @@ pub fn fequal(a: f32, b: f32) -> bool {
         return true;
     }
 }
-
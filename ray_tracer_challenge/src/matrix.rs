@@ -1,5 +1,9 @@
 use crate::math::fequal;
+use crate::math::Float;
 use crate::tuple::Tuple;
+use crate::tuple::Vector;
+use crate::quaternion::Quaternion;
+use crate::stats;
 use crate::tuple;
 use crate::point;
 use crate::vector;
@@ -8,14 +12,14 @@ use crate::vector;
 // #[repr(C, packed)]
 #[derive(Debug)]
 struct Matrix2x2 {
-    r1c1: f32, r1c2: f32,
-    r2c1: f32, r2c2: f32
+    r1c1: Float, r1c2: Float,
+    r2c1: Float, r2c2: Float
 }
 
 impl Matrix2x2 {
-    fn from_f32( 
-            a: f32, b: f32, 
-            c: f32, d: f32) -> Matrix2x2 {
+    fn from_float( 
+            a: Float, b: Float, 
+            c: Float, d: Float) -> Matrix2x2 {
         Matrix2x2 {
             r1c1: a, r1c2: b,
             r2c1: c, r2c2: d
@@ -25,9 +29,9 @@ impl Matrix2x2 {
     fn from_i32 (
             a: i32, b: i32,
             c: i32, d: i32) -> Matrix2x2 {
-        Matrix2x2::from_f32(
-            a as f32, b as f32, 
-            c as f32, d as f32) 
+        Matrix2x2::from_float(
+            a as Float, b as Float, 
+            c as Float, d as Float) 
     }
 
     fn multiply(&self, rhs: &Matrix2x2) -> Matrix2x2 {
@@ -40,13 +44,13 @@ impl Matrix2x2 {
     }
 
     fn transpose(&self) -> Matrix2x2 {
-        Matrix2x2::from_f32(
+        Matrix2x2::from_float(
             self.r1c1, self.r2c1,
             self.r1c2, self.r2c2
         )
     }
 
-    fn determinant(&self) -> f32 {
+    fn determinant(&self) -> Float {
         self.r1c1 * self.r2c2 - self.r1c2 * self.r2c1
     }
 }
@@ -63,16 +67,16 @@ impl PartialEq for Matrix2x2 {
 // #[repr(C, packed)]
 #[derive(Debug)]
 pub struct Matrix3x3 {
-    r1c1: f32, r1c2: f32, r1c3: f32,
-    r2c1: f32, r2c2: f32, r2c3: f32,
-    r3c1: f32, r3c2: f32, r3c3: f32
+    r1c1: Float, r1c2: Float, r1c3: Float,
+    r2c1: Float, r2c2: Float, r2c3: Float,
+    r3c1: Float, r3c2: Float, r3c3: Float
 }
 
 impl Matrix3x3 {
-    pub fn from_f32(
-            a: f32, b: f32, c: f32, 
-            d: f32, e: f32, f: f32, 
-            g: f32, h: f32, i: f32) -> Matrix3x3 {
+    pub fn from_float(
+            a: Float, b: Float, c: Float, 
+            d: Float, e: Float, f: Float, 
+            g: Float, h: Float, i: Float) -> Matrix3x3 {
         Matrix3x3 {
             r1c1: a, r1c2: b, r1c3: c,
             r2c1: d, r2c2: e, r2c3: f,
@@ -84,10 +88,10 @@ impl Matrix3x3 {
             a: i32, b: i32, c: i32, 
             d: i32, e: i32, f: i32, 
             g: i32, h: i32, i: i32) -> Matrix3x3 {
-        Matrix3x3::from_f32(
-            a as f32, b as f32, c as f32,
-            d as f32, e as f32, f as f32,
-            g as f32, h as f32, i as f32
+        Matrix3x3::from_float(
+            a as Float, b as Float, c as Float,
+            d as Float, e as Float, f as Float,
+            g as Float, h as Float, i as Float
         )
     }
 
@@ -106,14 +110,14 @@ impl Matrix3x3 {
     }
 
     fn transpose(&self) -> Matrix3x3 {
-        Matrix3x3::from_f32(
+        Matrix3x3::from_float(
             self.r1c1, self.r2c1, self.r3c1,
             self.r1c2, self.r2c2, self.r3c2,
             self.r1c3, self.r2c3, self.r3c3
         )
     }
     
-    fn determinant(&self) -> f32 {
+    fn determinant(&self) -> Float {
         let a = self.cofactor(0, 0);
         let b = self.cofactor(0, 1);
         let c = self.cofactor(0, 2);
@@ -131,7 +135,7 @@ impl Matrix3x3 {
             self.r1c1, self.r1c2, self.r1c3,
             self.r2c1, self.r2c2, self.r2c3,
             self.r3c1, self.r3c2, self.r3c3];
-        let mut t: [f32; 4] = [0.0; 4];
+        let mut t: [Float; 4] = [0.0; 4];
         let mut t_index = 0;
         for y in 0..3 {
             for x in 0..3 {
@@ -142,7 +146,7 @@ impl Matrix3x3 {
                 }
             }
         }
-        Matrix2x2::from_f32(t[0],t[1],t[2],t[3])
+        Matrix2x2::from_float(t[0],t[1],t[2],t[3])
     }
 
     /*
@@ -150,12 +154,12 @@ impl Matrix3x3 {
 
         the minor is the determinant of the submatrix at row, col
     */
-    fn minor(&self, row: u8, col: u8) -> f32 {
+    fn minor(&self, row: u8, col: u8) -> Float {
         let submatrix = self.submatrix(row, col);
         submatrix.determinant()
     }
 
-    fn cofactor(&self, row: u8, col: u8) -> f32 {
+    fn cofactor(&self, row: u8, col: u8) -> Float {
         let minor = self.minor(row, col);
         cofactor(minor, row, col)
     }
@@ -176,20 +180,20 @@ impl PartialEq for Matrix3x3 {
 }
 
 // #[repr(C, packed)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Matrix4x4 {
-    r1c1: f32, r1c2: f32, r1c3: f32, r1c4: f32,
-    r2c1: f32, r2c2: f32, r2c3: f32, r2c4: f32,
-    r3c1: f32, r3c2: f32, r3c3: f32, r3c4: f32,
-    r4c1: f32, r4c2: f32, r4c3: f32, r4c4: f32
+    r1c1: Float, r1c2: Float, r1c3: Float, r1c4: Float,
+    r2c1: Float, r2c2: Float, r2c3: Float, r2c4: Float,
+    r3c1: Float, r3c2: Float, r3c3: Float, r3c4: Float,
+    r4c1: Float, r4c2: Float, r4c3: Float, r4c4: Float
 }
 
 impl Matrix4x4 {
-    fn from_f32(
-        a: f32, b: f32, c: f32, d: f32, 
-        e: f32, f: f32, g: f32, h: f32,
-        i: f32, j: f32, k: f32, l: f32,
-        m: f32, n: f32, o: f32, p: f32) -> Matrix4x4 {
+    fn from_float(
+        a: Float, b: Float, c: Float, d: Float, 
+        e: Float, f: Float, g: Float, h: Float,
+        i: Float, j: Float, k: Float, l: Float,
+        m: Float, n: Float, o: Float, p: Float) -> Matrix4x4 {
         Matrix4x4 {
             r1c1: a, r1c2: b, r1c3: c, r1c4: d,
             r2c1: e, r2c2: f, r2c3: g, r2c4: h,
@@ -203,11 +207,11 @@ impl Matrix4x4 {
         e: i32, f: i32, g: i32, h: i32,
         i: i32, j: i32, k: i32, l: i32,
         m: i32, n: i32, o: i32, p: i32) -> Matrix4x4 {
-        Matrix4x4::from_f32 (
-            a as f32, b as f32, c as f32, d as f32,
-            e as f32, f as f32, g as f32, h as f32,
-            i as f32, j as f32, k as f32, l as f32,
-            m as f32, n as f32, o as f32, p as f32
+        Matrix4x4::from_float (
+            a as Float, b as Float, c as Float, d as Float,
+            e as Float, f as Float, g as Float, h as Float,
+            i as Float, j as Float, k as Float, l as Float,
+            m as Float, n as Float, o as Float, p as Float
         )
     }
 
@@ -242,7 +246,7 @@ impl Matrix4x4 {
     }
 
     pub fn transpose(&self) -> Matrix4x4 {
-        Matrix4x4::from_f32(
+        Matrix4x4::from_float(
             self.r1c1, self.r2c1, self.r3c1, self.r4c1,
             self.r1c2, self.r2c2, self.r3c2, self.r4c2,
             self.r1c3, self.r2c3, self.r3c3, self.r4c3,
@@ -250,7 +254,7 @@ impl Matrix4x4 {
         )
     }
     
-    fn determinant(&self) -> f32 {
+    fn determinant(&self) -> Float {
         let a = self.cofactor(0, 0);
         let b = self.cofactor(0, 1);
         let c = self.cofactor(0, 2);
@@ -270,7 +274,7 @@ impl Matrix4x4 {
             self.r2c1, self.r2c2, self.r2c3, self.r2c4,
             self.r3c1, self.r3c2, self.r3c3, self.r3c4,
             self.r4c1, self.r4c2, self.r4c3, self.r4c4];
-        let mut t: [f32; 9] = [0.0; 9];
+        let mut t: [Float; 9] = [0.0; 9];
         let mut t_index = 0;
         for y in 0..4 {
             for x in 0..4 {
@@ -281,26 +285,27 @@ impl Matrix4x4 {
                 }
             }
         }
-        Matrix3x3::from_f32(t[0],t[1],t[2],t[3],t[4],t[5],t[6],t[7],t[8])
+        Matrix3x3::from_float(t[0],t[1],t[2],t[3],t[4],t[5],t[6],t[7],t[8])
     }
     
     // determinant of the submatrix
-    fn minor(&self, row: u8, col: u8) -> f32 {
+    fn minor(&self, row: u8, col: u8) -> Float {
         let submatrix = self.submatrix(row, col);
         submatrix.determinant()
     }
 
-    fn cofactor(&self, row: u8, col: u8) -> f32 {
+    fn cofactor(&self, row: u8, col: u8) -> Float {
         let minor = self.minor(row, col);
         cofactor(minor, row, col)
     }
     
     pub fn inverse(&self) -> Matrix4x4 {
+        stats::count_matrix_inversion();
         let determinant = self.determinant();
         if determinant == 0.0 {
             panic!("Attempting to invert and invertible matrix");
         }
-        let mut t: [f32; 16] = [0.0; 16];
+        let mut t: [Float; 16] = [0.0; 16];
         for row in 0..4 {
             for col in 0..4 {
                 let c = self.cofactor(row, col);
@@ -308,10 +313,10 @@ impl Matrix4x4 {
                 t[t_index] = c / determinant;
             }
         }
-        Matrix4x4::from_f32(t[0],t[1],t[2],t[3],t[4],t[5],t[6],t[7],t[8],t[9],t[10],t[11],t[12],t[13],t[14],t[15])
+        Matrix4x4::from_float(t[0],t[1],t[2],t[3],t[4],t[5],t[6],t[7],t[8],t[9],t[10],t[11],t[12],t[13],t[14],t[15])
     }
 
-    pub fn translation(x: f32, y: f32, z: f32) -> Matrix4x4 {
+    pub fn translation(x: Float, y: Float, z: Float) -> Matrix4x4 {
         let mut a = MATRIX_4X4_IDENTITY;
         a.r1c4 = x;
         a.r2c4 = y;
@@ -319,7 +324,7 @@ impl Matrix4x4 {
         return a;
     }
     
-    pub fn scaling(x: f32, y: f32, z: f32) -> Matrix4x4 {
+    pub fn scaling(x: Float, y: Float, z: Float) -> Matrix4x4 {
         let mut a = MATRIX_4X4_IDENTITY;
         a.r1c1 = x;
         a.r2c2 = y;
@@ -327,7 +332,7 @@ impl Matrix4x4 {
         return a;
     }
 
-    pub fn rotation_x(r: f32) -> Matrix4x4 {
+    pub fn rotation_x(r: Float) -> Matrix4x4 {
         let mut a = MATRIX_4X4_IDENTITY;
         let cos_r = r.cos();
         let sin_r = r.sin();
@@ -338,7 +343,7 @@ impl Matrix4x4 {
         return a;
     }
 
-    pub fn rotation_y(r: f32) -> Matrix4x4 {
+    pub fn rotation_y(r: Float) -> Matrix4x4 {
         let mut a = MATRIX_4X4_IDENTITY;
         let cos_r = r.cos();
         let sin_r = r.sin();
@@ -349,7 +354,7 @@ impl Matrix4x4 {
         return a;
     }
 
-    pub fn rotation_z(r: f32) -> Matrix4x4 {
+    pub fn rotation_z(r: Float) -> Matrix4x4 {
         let mut a = MATRIX_4X4_IDENTITY;
         let cos_r = r.cos();
         let sin_r = r.sin();
@@ -360,7 +365,7 @@ impl Matrix4x4 {
         return a;
     }
 
-    pub fn shearing(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Matrix4x4 {
+    pub fn shearing(xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Matrix4x4 {
         let mut a = MATRIX_4X4_IDENTITY;
         a.r1c2 = xy;
         a.r1c3 = xz;
@@ -371,12 +376,122 @@ impl Matrix4x4 {
         return a;
     }
 
+    /*
+        rotation of r radians around an arbitrary axis
+        the axis does not need to be normalized
+    */
+    pub fn rotation_axis_angle(axis: &Vector, r: Float) -> Matrix4x4 {
+        let n = axis.normalize();
+        let cos_r = r.cos();
+        let sin_r = r.sin();
+        let t = 1.0 - cos_r;
+        let mut a = MATRIX_4X4_IDENTITY;
+        a.r1c1 = t * n.x * n.x + cos_r;
+        a.r1c2 = t * n.x * n.y - sin_r * n.z;
+        a.r1c3 = t * n.x * n.z + sin_r * n.y;
+        a.r2c1 = t * n.x * n.y + sin_r * n.z;
+        a.r2c2 = t * n.y * n.y + cos_r;
+        a.r2c3 = t * n.y * n.z - sin_r * n.x;
+        a.r3c1 = t * n.x * n.z - sin_r * n.y;
+        a.r3c2 = t * n.y * n.z + sin_r * n.x;
+        a.r3c3 = t * n.z * n.z + cos_r;
+        a
+    }
+
+    pub fn rotation_quaternion(q: &Quaternion) -> Matrix4x4 {
+        let q = q.normalize();
+        let mut a = MATRIX_4X4_IDENTITY;
+        a.r1c1 = 1.0 - 2.0 * (q.y * q.y + q.z * q.z);
+        a.r1c2 = 2.0 * (q.x * q.y - q.z * q.w);
+        a.r1c3 = 2.0 * (q.x * q.z + q.y * q.w);
+        a.r2c1 = 2.0 * (q.x * q.y + q.z * q.w);
+        a.r2c2 = 1.0 - 2.0 * (q.x * q.x + q.z * q.z);
+        a.r2c3 = 2.0 * (q.y * q.z - q.x * q.w);
+        a.r3c1 = 2.0 * (q.x * q.z - q.y * q.w);
+        a.r3c2 = 2.0 * (q.y * q.z + q.x * q.w);
+        a.r3c3 = 1.0 - 2.0 * (q.x * q.x + q.y * q.y);
+        a
+    }
+
+    /*
+        the rotation held in the upper 3x3 as a quaternion
+        assumes the upper 3x3 is a pure rotation, use decompose
+        when the matrix also has scale
+    */
+    pub fn to_quaternion(&self) -> Quaternion {
+        let trace = self.r1c1 + self.r2c2 + self.r3c3;
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                0.25 * s,
+                (self.r3c2 - self.r2c3) / s,
+                (self.r1c3 - self.r3c1) / s,
+                (self.r2c1 - self.r1c2) / s)
+        }
+        else if self.r1c1 > self.r2c2 && self.r1c1 > self.r3c3 {
+            let s = (1.0 + self.r1c1 - self.r2c2 - self.r3c3).sqrt() * 2.0;
+            Quaternion::new(
+                (self.r3c2 - self.r2c3) / s,
+                0.25 * s,
+                (self.r1c2 + self.r2c1) / s,
+                (self.r1c3 + self.r3c1) / s)
+        }
+        else if self.r2c2 > self.r3c3 {
+            let s = (1.0 + self.r2c2 - self.r1c1 - self.r3c3).sqrt() * 2.0;
+            Quaternion::new(
+                (self.r1c3 - self.r3c1) / s,
+                (self.r1c2 + self.r2c1) / s,
+                0.25 * s,
+                (self.r2c3 + self.r3c2) / s)
+        }
+        else {
+            let s = (1.0 + self.r3c3 - self.r1c1 - self.r2c2).sqrt() * 2.0;
+            Quaternion::new(
+                (self.r2c1 - self.r1c2) / s,
+                (self.r1c3 + self.r3c1) / s,
+                (self.r2c3 + self.r3c2) / s,
+                0.25 * s)
+        };
+        q.normalize()
+    }
+
+    /*
+        splits an affine transform back into translation, rotation and scale
+        so that translation * rotation * scale rebuilds it
+
+        shearing can't be represented and is lost, a mirrored
+        transform comes back with a negative x scale
+    */
+    pub fn decompose(&self) -> Decomposition {
+        let translation = vector!(self.r1c4, self.r2c4, self.r3c4);
+        let mut sx = vector!(self.r1c1, self.r2c1, self.r3c1).magnitude();
+        let sy = vector!(self.r1c2, self.r2c2, self.r3c2).magnitude();
+        let sz = vector!(self.r1c3, self.r2c3, self.r3c3).magnitude();
+        let upper = Matrix3x3::from_float(
+            self.r1c1, self.r1c2, self.r1c3,
+            self.r2c1, self.r2c2, self.r2c3,
+            self.r3c1, self.r3c2, self.r3c3);
+        if upper.determinant() < 0.0 {
+            sx = -sx;
+        }
+        let rotation = Matrix4x4::from_float(
+            self.r1c1 / sx, self.r1c2 / sy, self.r1c3 / sz, 0.0,
+            self.r2c1 / sx, self.r2c2 / sy, self.r2c3 / sz, 0.0,
+            self.r3c1 / sx, self.r3c2 / sy, self.r3c3 / sz, 0.0,
+            0.0,            0.0,            0.0,            1.0);
+        Decomposition {
+            translation,
+            rotation: rotation.to_quaternion(),
+            scale: vector!(sx, sy, sz)
+        }
+    }
+
     pub fn view_transform(from: &Tuple, to: &Tuple, up: &Tuple) -> Matrix4x4 {
         let forwardv = to.subtract(&from).normalize();
         let upn = up.normalize();
         let leftv = forwardv.cross(&upn);
         let true_upv = leftv.cross(&forwardv);
-        let orientation = Matrix4x4::from_f32(
+        let orientation = Matrix4x4::from_float(
             leftv.x,     leftv.y,     leftv.z,      0.0,
             true_upv.x,  true_upv.y,  true_upv.z,   0.0,
             -forwardv.x, -forwardv.y, -forwardv.z,  0.0,
@@ -408,6 +523,33 @@ impl PartialEq for Matrix4x4 {
     }
 }
 
+pub struct Decomposition {
+    pub translation: Vector,
+    pub rotation: Quaternion,
+    pub scale: Vector
+}
+
+impl Decomposition {
+    pub fn to_matrix(&self) -> Matrix4x4 {
+        Matrix4x4::translation(self.translation.x, self.translation.y, self.translation.z)
+            .multiply(&self.rotation.to_matrix())
+            .multiply(&Matrix4x4::scaling(self.scale.x, self.scale.y, self.scale.z))
+    }
+
+    /*
+        the transform a fraction t of the way from self to other,
+        translation and scale move in a straight line and the
+        rotation along the shortest arc
+    */
+    pub fn interpolate(&self, other: &Decomposition, t: Float) -> Decomposition {
+        Decomposition {
+            translation: self.translation.add(&other.translation.subtract(&self.translation).multiplyf(t)),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale.add(&other.scale.subtract(&self.scale).multiplyf(t))
+        }
+    }
+}
+
 const MATRIX_2X2_IDENTITY : Matrix2x2 = Matrix2x2 {
     r1c1: 1.0, r1c2: 0.0,
     r2c1: 0.0, r2c2: 1.0
@@ -426,7 +568,7 @@ pub const MATRIX_4X4_IDENTITY : Matrix4x4 = Matrix4x4 {
     r4c1: 0.0, r4c2: 0.0, r4c3: 0.0, r4c4: 1.0
 };
 
-fn cofactor(minor: f32, row: u8, col: u8) -> f32 {
+fn cofactor(minor: Float, row: u8, col: u8) -> Float {
     if (row + col) % 2 == 0 {
         return minor;
     }
@@ -438,7 +580,7 @@ fn cofactor(minor: f32, row: u8, col: u8) -> f32 {
 #[test]
 fn matrix2x2_create_test() {
     let a = Matrix2x2::from_i32(1,2,3,4);
-    let b = Matrix2x2::from_f32(1.0, 2.0, 3.0, 4.0);
+    let b = Matrix2x2::from_float(1.0, 2.0, 3.0, 4.0);
     let c = Matrix2x2::from_i32(-1,2,3,4);
     let d = Matrix2x2::from_i32(1,-1,3,4);
     let e = Matrix2x2::from_i32(1,2,-1,4);
@@ -453,7 +595,7 @@ fn matrix2x2_create_test() {
 #[test]
 fn matrix3x3_create_test() {
     let a = Matrix3x3::from_i32(1,2,3,4,5,6,7,8,9);
-    let b = Matrix3x3::from_f32(1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0);
+    let b = Matrix3x3::from_float(1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0);
     let c = Matrix3x3::from_i32(-1,2,3,4,5,6,7,8,9);
     let d = Matrix3x3::from_i32(1,-1,3,4,5,6,7,8,9);
     let e = Matrix3x3::from_i32(1,2,-1,4,5,6,7,8,9);
@@ -478,7 +620,7 @@ fn matrix3x3_create_test() {
 #[test]
 fn matrix4x4_create_test() {
     let a = Matrix4x4::from_i32(1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16);
-    let b = Matrix4x4::from_f32(1.0,2.0,3.0,4.0,5.0, 6.0,7.0,8.0,9.0,10.0,11.0,12.0,13.0,14.0,15.0,16.0);
+    let b = Matrix4x4::from_float(1.0,2.0,3.0,4.0,5.0, 6.0,7.0,8.0,9.0,10.0,11.0,12.0,13.0,14.0,15.0,16.0);
     let c = Matrix4x4::from_i32(-1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16);
     let d = Matrix4x4::from_i32(1,-1,3,4,5,6,7,8,9,10,11,12,13,14,15,16);
     let e = Matrix4x4::from_i32(1,2,-1,4,5,6,7,8,9,10,11,12,13,14,15,16);
@@ -652,7 +794,7 @@ fn inverse_test1() {
     assert!(fequal(b.r4c3, -160.0/532.0));
     assert_eq!(a.cofactor(3,2), 105.0);
     assert!(fequal(b.r3c4, 105.0/532.0));
-    let c = Matrix4x4::from_f32(
+    let c = Matrix4x4::from_float(
         0.21805, 0.45113, 0.24060, -0.04511,
         -0.80827, -1.45677, -0.44361, 0.52068,
         -0.07895, -0.22368, -0.05263, 0.19737,
@@ -664,7 +806,7 @@ fn inverse_test1() {
 fn inverse_test2() {
     let a = Matrix4x4::from_i32(8,-5,9,2,7,5,6,1,-6,0,9,6,-3,0,-9,-4);
     let b = a.inverse();
-    let c = Matrix4x4::from_f32(
+    let c = Matrix4x4::from_float(
         -0.15385, -0.15385, -0.28205, -0.53846,
         -0.07692, 0.12308, 0.02564, 0.03077,
         0.35897, 0.35897, 0.43590, 0.92308,
@@ -676,7 +818,7 @@ fn inverse_test2() {
 fn inverse_test3() {
     let a = Matrix4x4::from_i32(9,3,0,9,-5,-2,-6,-3,-4,9,6,4,-7,6,6,2);
     let b = a.inverse();
-    let c = Matrix4x4::from_f32(
+    let c = Matrix4x4::from_float(
         -0.04074, -0.07778, 0.14444, -0.22222,
         -0.07778, 0.03333, 0.36667, -0.33333,
         -0.02901, -0.14630, -0.10926, 0.12963,
@@ -731,11 +873,11 @@ fn scaling_test() {
 
 #[test]
 fn rotation_x_test() {
-    let sqrt2div2 = 2_f32.sqrt()/2.0;
+    let sqrt2div2 = (2.0 as Float).sqrt()/2.0;
     let p = point!(0,1,0);
-    let half_quarter = Matrix4x4::rotation_x(std::f32::consts::PI / 4.0);
+    let half_quarter = Matrix4x4::rotation_x(crate::math::consts::PI / 4.0);
     let inv_half_quarter = half_quarter.inverse();
-    let full_quarter = Matrix4x4::rotation_x(std::f32::consts::PI / 2.0);
+    let full_quarter = Matrix4x4::rotation_x(crate::math::consts::PI / 2.0);
     assert_eq!(half_quarter.multiply_tuple(&p), point!(0, sqrt2div2, sqrt2div2));
     assert_eq!(inv_half_quarter.multiply_tuple(&p), point!(0, sqrt2div2, -sqrt2div2));
     assert_eq!(full_quarter.multiply_tuple(&p), point!(0, 0, 1));
@@ -743,20 +885,20 @@ fn rotation_x_test() {
 
 #[test]
 fn rotation_y_test() {
-    let sqrt2div2 = 2_f32.sqrt()/2.0;
+    let sqrt2div2 = (2.0 as Float).sqrt()/2.0;
     let p = point!(0,0,1);
-    let half_quarter = Matrix4x4::rotation_y(std::f32::consts::PI / 4.0);
-    let full_quarter = Matrix4x4::rotation_y(std::f32::consts::PI / 2.0);
+    let half_quarter = Matrix4x4::rotation_y(crate::math::consts::PI / 4.0);
+    let full_quarter = Matrix4x4::rotation_y(crate::math::consts::PI / 2.0);
     assert_eq!(half_quarter.multiply_tuple(&p), point!(sqrt2div2, 0, sqrt2div2));
     assert_eq!(full_quarter.multiply_tuple(&p), point!(1, 0, 0));
 }
 
 #[test]
 fn rotation_z_test() {
-    let sqrt2div2 = 2_f32.sqrt()/2.0;
+    let sqrt2div2 = (2.0 as Float).sqrt()/2.0;
     let p = point!(0,1,0);
-    let half_quarter = Matrix4x4::rotation_z(std::f32::consts::PI / 4.0);
-    let full_quarter = Matrix4x4::rotation_z(std::f32::consts::PI / 2.0);
+    let half_quarter = Matrix4x4::rotation_z(crate::math::consts::PI / 4.0);
+    let full_quarter = Matrix4x4::rotation_z(crate::math::consts::PI / 2.0);
     assert_eq!(half_quarter.multiply_tuple(&p), point!(-sqrt2div2, sqrt2div2, 0));
     assert_eq!(full_quarter.multiply_tuple(&p), point!(-1, 0, 0));
 }
@@ -817,11 +959,46 @@ fn view_transform_test() {
     let to = point!(4,-2,8);
     let up = vector!(1,1,0);
     let transform = Matrix4x4::view_transform(&from, &to, &up);
-    let t = Matrix4x4::from_f32(
+    let t = Matrix4x4::from_float(
         -0.50709, 0.50709, 0.67612, -2.36643,
         0.76772, 0.60609, 0.12122, -2.82843,
         -0.35857, 0.59761, -0.71714, 0.0,
         0.0, 0.0, 0.0, 1.0
     );
     assert_eq!(transform, t);
-}
\ No newline at end of file
+}
+
+#[test]
+fn rotation_axis_angle_test() {
+    let quarter_pi = crate::math::consts::PI / 4.0;
+
+    // the principal axes match the dedicated rotations
+    assert_eq!(Matrix4x4::rotation_axis_angle(&vector!(1,0,0), quarter_pi), Matrix4x4::rotation_x(quarter_pi));
+    assert_eq!(Matrix4x4::rotation_axis_angle(&vector!(0,1,0), quarter_pi), Matrix4x4::rotation_y(quarter_pi));
+    assert_eq!(Matrix4x4::rotation_axis_angle(&vector!(0,0,3), quarter_pi), Matrix4x4::rotation_z(quarter_pi));
+
+    // a third of a turn around the diagonal cycles the axes
+    let r = Matrix4x4::rotation_axis_angle(&vector!(1,1,1), crate::math::consts::PI * 2.0 / 3.0);
+    assert_eq!(r.multiply_tuple(&point!(1,0,0)), point!(0,1,0));
+    assert_eq!(r.multiply_tuple(&vector!(0,1,0)), vector!(0,0,1));
+    assert_eq!(r.to_quaternion().to_matrix(), r);
+}
+
+#[test]
+fn decompose_test() {
+    let rotation = Matrix4x4::rotation_axis_angle(&vector!(1,2,3), 0.7);
+    let m = Matrix4x4::translation(1.0, -2.0, 3.0)
+        .multiply(&rotation)
+        .multiply(&Matrix4x4::scaling(2.0, 0.5, 4.0));
+    let d = m.decompose();
+    assert_eq!(d.translation, vector!(1,-2,3));
+    assert_eq!(d.scale, vector!(2,0.5,4));
+    assert_eq!(d.rotation.to_matrix(), rotation);
+    assert_eq!(d.to_matrix(), m);
+
+    // mirroring shows up as a negative scale
+    let m = Matrix4x4::scaling(-1.0, 1.0, 1.0);
+    let d = m.decompose();
+    assert_eq!(d.scale, vector!(-1,1,1));
+    assert_eq!(d.to_matrix(), m);
+}
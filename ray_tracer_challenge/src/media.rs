@@ -0,0 +1,117 @@
+use crate::color::Color;
+use crate::math::Float;
+use crate::ray::Ray;
+use crate::world;
+use crate::world::World;
+
+// points along a ray at which a volume gathers light
+const VOLUME_STEPS : u32 = 16;
+
+/*
+    exponential distance fog filling the whole world, after a
+    distance d only exp(-density * d) of what is behind is left
+    and the rest is the fog's color
+*/
+pub struct Fog {
+    pub color: Color,
+    pub density: Float
+}
+
+impl Fog {
+    pub fn new(color: Color, density: Float) -> Fog {
+        Fog {
+            color,
+            density
+        }
+    }
+
+    // no density lets everything through, even over an infinite distance
+    pub fn transmittance(&self, distance: Float) -> Float {
+        if self.density <= 0.0 {
+            return 1.0;
+        }
+        (-self.density * distance).exp()
+    }
+
+    // color seen through distance of fog, an infinite distance gives just the fog
+    pub fn apply(&self, color: &Color, distance: Float) -> Color {
+        let transmittance = self.transmittance(distance);
+        color.multiplyf(transmittance).add(&self.color.multiplyf(1.0 - transmittance))
+    }
+}
+
+/*
+    a homogeneous medium such as smoke filling a closed shape,
+    see Material::volume
+
+    density is how much light is absorbed or scattered per unit
+    distance and albedo the color of the scattered part, light is
+    scattered equally in all directions and, like the diffuse term
+    of light::lighting, a light's intensity is taken as the light
+    arriving so a dense white volume in front of a light shows about
+    its intensity
+*/
+pub struct Volume {
+    pub density: Float,
+    pub albedo: Color
+}
+
+impl Volume {
+    pub fn new(density: Float, albedo: Color) -> Volume {
+        Volume {
+            density,
+            albedo
+        }
+    }
+
+    // no density lets everything through, even over an infinite distance
+    pub fn transmittance(&self, distance: Float) -> Float {
+        if self.density <= 0.0 {
+            return 1.0;
+        }
+        (-self.density * distance).exp()
+    }
+
+    /*
+        the light reaching the origin of ray from the part of the
+        volume between t = 0 and t = length, with behind being
+        the color seen past it
+
+        the light scattered towards the ray is gathered at a fixed
+        number of points each with its own shadow ray
+    */
+    pub(crate) fn integrate(&self, world: &World, ray: &Ray, length: Float, behind: &Color) -> Color {
+        let speed = ray.direction.magnitude();
+        let step = length / VOLUME_STEPS as Float;
+        let mut scattered = Color::BLACK;
+        for i in 0..VOLUME_STEPS {
+            let t = (i as Float + 0.5) * step;
+            let point = ray.position(t);
+            let mut arriving = Color::BLACK;
+            for light in world.lights.iter() {
+                let transmittance = world::shadow_transmittance(world, &point, light, ray.time);
+                arriving = arriving.add(&light.intensity.hadamard(&transmittance));
+            }
+            let weight = self.density * step * speed * self.transmittance(t * speed);
+            scattered = scattered.add(&arriving.hadamard(&self.albedo).multiplyf(weight));
+        }
+        scattered.add(&behind.multiplyf(self.transmittance(length * speed)))
+    }
+}
+
+#[test]
+fn fog_test() {
+    let fog = Fog::new(crate::rgb!(0.5,0.5,0.5), 0.5);
+    assert_eq!(fog.transmittance(0.0), 1.0);
+    assert!(crate::math::fequal(fog.transmittance(2.0), (-1.0 as Float).exp()));
+    assert_eq!(fog.apply(&Color::WHITE, 0.0), Color::WHITE);
+    assert_eq!(fog.apply(&Color::WHITE, Float::INFINITY), crate::rgb!(0.5,0.5,0.5));
+    let t = (-1.0 as Float).exp();
+    assert_eq!(fog.apply(&Color::BLACK, 2.0), crate::rgb!(0.5 - 0.5 * t, 0.5 - 0.5 * t, 0.5 - 0.5 * t));
+
+    // clear air, 0 times infinity is not a number
+    let clear = Fog::new(crate::rgb!(0.5,0.5,0.5), 0.0);
+    assert_eq!(clear.transmittance(Float::INFINITY), 1.0);
+    assert_eq!(clear.apply(&Color::WHITE, Float::INFINITY), Color::WHITE);
+    assert_eq!(Volume::new(0.0, Color::WHITE).transmittance(Float::INFINITY), 1.0);
+}
@@ -0,0 +1,175 @@
+use crate::math::Float;
+use crate::tuple::Point;
+
+/*
+    Ken Perlin's improved gradient noise
+
+    https://mrl.cs.nyu.edu/~perlin/noise/
+*/
+
+const PERMUTATION : [u8; 256] = [
+    151,160,137,91,90,15,131,13,201,95,96,53,194,233,7,225,140,36,103,30,69,142,
+    8,99,37,240,21,10,23,190,6,148,247,120,234,75,0,26,197,62,94,252,219,203,117,
+    35,11,32,57,177,33,88,237,149,56,87,174,20,125,136,171,168,68,175,74,165,71,
+    134,139,48,27,166,77,146,158,231,83,111,229,122,60,211,133,230,220,105,92,41,
+    55,46,245,40,244,102,143,54,65,25,63,161,1,216,80,73,209,76,132,187,208,89,
+    18,169,200,196,135,130,116,188,159,86,164,100,109,198,173,186,3,64,52,217,226,
+    250,124,123,5,202,38,147,118,126,255,82,85,212,207,206,59,227,47,16,58,17,182,
+    189,28,42,223,183,170,213,119,248,152,2,44,154,163,70,221,153,101,155,167,43,
+    172,9,129,22,39,253,19,98,108,110,79,113,224,232,178,185,112,104,218,246,97,
+    228,251,34,242,193,238,210,144,12,191,179,162,241,81,51,145,235,249,14,239,
+    107,49,192,214,31,181,199,106,157,184,84,204,176,115,121,50,45,127,4,150,254,
+    138,236,205,93,222,114,67,29,24,72,243,141,128,195,78,66,215,61,156,180
+];
+
+fn perm(i: i32) -> i32 {
+    PERMUTATION[(i & 255) as usize] as i32
+}
+
+// 6t^5 - 15t^4 + 10t^3, eases the lattice weights so the noise is smooth
+fn fade(t: Float) -> Float {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: Float, a: Float, b: Float) -> Float {
+    a + t * (b - a)
+}
+
+// dot product of the distance vector with one of 12 edge gradients picked by the hash
+fn grad(hash: i32, x: Float, y: Float, z: Float) -> Float {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    let a = if h & 1 == 0 { u } else { -u };
+    let b = if h & 2 == 0 { v } else { -v };
+    a + b
+}
+
+/*
+    noise value at x, y, z in roughly -1 to 1
+
+    the value is 0 on every integer lattice point and varies
+    smoothly in between, so scale the input to change the feature size
+*/
+pub fn noise(x: Float, y: Float, z: Float) -> Float {
+    let xf = x.floor();
+    let yf = y.floor();
+    let zf = z.floor();
+    let xi = xf as i32;
+    let yi = yf as i32;
+    let zi = zf as i32;
+
+    // position inside the unit cube
+    let x = x - xf;
+    let y = y - yf;
+    let z = z - zf;
+    let u = fade(x);
+    let v = fade(y);
+    let w = fade(z);
+
+    // hash the 8 corners of the cube
+    let a = perm(xi) + yi;
+    let aa = perm(a) + zi;
+    let ab = perm(a + 1) + zi;
+    let b = perm(xi + 1) + yi;
+    let ba = perm(b) + zi;
+    let bb = perm(b + 1) + zi;
+
+    lerp(w,
+        lerp(v,
+            lerp(u, grad(perm(aa), x, y, z), grad(perm(ba), x - 1.0, y, z)),
+            lerp(u, grad(perm(ab), x, y - 1.0, z), grad(perm(bb), x - 1.0, y - 1.0, z))),
+        lerp(v,
+            lerp(u, grad(perm(aa + 1), x, y, z - 1.0), grad(perm(ba + 1), x - 1.0, y, z - 1.0)),
+            lerp(u, grad(perm(ab + 1), x, y - 1.0, z - 1.0), grad(perm(bb + 1), x - 1.0, y - 1.0, z - 1.0))))
+}
+
+pub fn noise_at(point: &Point) -> Float {
+    noise(point.x, point.y, point.z)
+}
+
+/*
+    fractal sum of noise octaves, each octave doubles the frequency
+    and halves the amplitude, the result stays in roughly -1 to 1
+*/
+pub fn fractal(point: &Point, octaves: u32) -> Float {
+    sum_octaves(point, octaves, |n| n)
+}
+
+/*
+    like fractal but sums the absolute value of each octave
+    giving the creased look of fire and marble veins, in 0 to 1
+*/
+pub fn turbulence(point: &Point, octaves: u32) -> Float {
+    sum_octaves(point, octaves, Float::abs)
+}
+
+fn sum_octaves(point: &Point, octaves: u32, shape: fn(Float) -> Float) -> Float {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max = 0.0;
+    let mut p = point.clone();
+    for _octave in 0..octaves {
+        total += amplitude * shape(noise_at(&p));
+        max += amplitude;
+        amplitude *= 0.5;
+        p = p.multiplyf(2.0);
+    }
+    if max == 0.0 {
+        0.0
+    }
+    else {
+        total / max
+    }
+}
+
+#[test]
+fn noise_lattice_test() {
+    // noise is zero on integer coordinates
+    assert_eq!(0.0, noise(0.0, 0.0, 0.0));
+    assert_eq!(0.0, noise(1.0, 2.0, 3.0));
+    assert_eq!(0.0, noise(-4.0, 7.0, -2.0));
+
+    // but not in between
+    assert!(noise(0.5, 0.5, 0.5) != 0.0 || noise(0.3, 0.7, 0.1) != 0.0);
+}
+
+#[test]
+fn noise_range_test() {
+    let mut min: Float = 0.0;
+    let mut max: Float = 0.0;
+    for i in 0..1000 {
+        let t = i as Float * 0.137;
+        let n = noise(t, t * 0.71 - 3.0, 5.0 - t * 0.33);
+        min = min.min(n);
+        max = max.max(n);
+        // the same input always gives the same value
+        assert_eq!(n, noise(t, t * 0.71 - 3.0, 5.0 - t * 0.33));
+    }
+    assert!((-1.0..-0.2).contains(&min));
+    assert!(max <= 1.0 && max > 0.2);
+}
+
+#[test]
+fn fractal_test() {
+    use crate::point;
+
+    let p = point!(0.3, 1.7, -2.2);
+    assert_eq!(fractal(&p, 1), noise_at(&p));
+    assert_eq!(fractal(&p, 0), 0.0);
+    let f = fractal(&p, 5);
+    assert!((-1.0..=1.0).contains(&f));
+}
+
+#[test]
+fn turbulence_test() {
+    use crate::point;
+
+    for i in 0..100 {
+        let t = i as Float * 0.29;
+        let v = turbulence(&point!(t, -t, t * 0.5), 4);
+        assert!((0.0..=1.0).contains(&v));
+    }
+    let p = point!(0.3, 1.7, -2.2);
+    assert_eq!(turbulence(&p, 1), noise_at(&p).abs());
+}
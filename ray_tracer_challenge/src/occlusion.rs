@@ -0,0 +1,132 @@
+use crate::color::Color;
+use crate::math::Float;
+use crate::ray::Ray;
+use crate::sampling;
+use crate::sampling::Rng;
+use crate::tuple::Point;
+use crate::tuple::Vector;
+use crate::world;
+use crate::world::World;
+
+/*
+    how much of the sky above a point is blocked by nearby geometry,
+    objects further than max_distance do not count so open rooms
+    are not darkened by their far walls
+*/
+pub struct AmbientOcclusion {
+    pub samples: u32,
+    pub max_distance: Float
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: u32, max_distance: Float) -> AmbientOcclusion {
+        AmbientOcclusion {
+            samples,
+            max_distance
+        }
+    }
+
+    /*
+        fraction of cosine weighted directions around normal that
+        leave point without hitting anything within max_distance,
+        1 is fully open and 0 fully enclosed
+
+        point should already be nudged off the surface, time is
+        that of the ray that found it
+    */
+    pub(crate) fn visibility(&self, world: &World, point: &Point, normal: &Vector, time: Float, rng: &mut Rng) -> Float {
+        if self.samples == 0 {
+            return 1.0;
+        }
+        let mut open = 0;
+        for _sample in 0..self.samples {
+            let direction = sampling::cosine_weighted_hemisphere(normal, rng);
+            let ray = Ray::at_time(point.clone(), direction, time);
+            let intersections = world::intersect(world, &ray);
+            match world::surface_hit(&intersections) {
+                Some(h) if h.t < self.max_distance => {},
+                _ => open += 1
+            }
+        }
+        open as Float / self.samples as Float
+    }
+
+    // the visibility of whatever ray sees as a grey level, white where it sees nothing
+    pub(crate) fn color_at(&self, world: &World, ray: &Ray, rng: &mut Rng) -> Color {
+        let intersections = world::intersect(world, ray);
+        match world::surface_hit(&intersections) {
+            None => Color::WHITE,
+            Some(intersection) => {
+                let comps = world::prepare_computations(ray, intersection);
+                let visibility = self.visibility(world, &comps.over_point, &comps.normalv, comps.time, rng);
+                Color::WHITE.multiplyf(visibility)
+            }
+        }
+    }
+}
+
+#[test]
+fn visibility_test() {
+    use crate::shape::Shape;
+    use crate::point;
+    use crate::vector;
+
+    let ao = AmbientOcclusion::new(64, 1000.0);
+    let mut rng = Rng::new(5);
+    let mut world = World::new();
+    world.objects.push(Shape::plane());
+
+    // nothing above a lone floor
+    let point = point!(0,0.0001,0);
+    let up = vector!(0,1,0);
+    assert_eq!(ao.visibility(&world, &point, &up, 0.0, &mut rng), 1.0);
+
+    // a ceiling within reach blocks everything
+    let mut ceiling = Shape::plane();
+    ceiling.transform = crate::matrix::Matrix4x4::translation(0.0, 0.5, 0.0);
+    world.objects.push(ceiling);
+    assert_eq!(ao.visibility(&world, &point, &up, 0.0, &mut rng), 0.0);
+
+    // only grazing rays get further than max_distance before reaching it
+    let ao = AmbientOcclusion::new(64, 1.0);
+    let visibility = ao.visibility(&world, &point, &up, 0.0, &mut rng);
+    assert!(visibility > 0.0 && visibility < 1.0);
+
+    // and none at all once it is out of reach
+    let ao = AmbientOcclusion::new(64, 0.25);
+    assert_eq!(ao.visibility(&world, &point, &up, 0.0, &mut rng), 1.0);
+
+    // a sphere resting on the floor darkens the floor right next to it
+    let mut world = World::new();
+    world.objects.push(Shape::plane());
+    let mut sphere = Shape::sphere();
+    sphere.transform = crate::matrix::Matrix4x4::translation(0.0, 1.0, 0.0);
+    world.objects.push(sphere);
+    let ao = AmbientOcclusion::new(256, 10.0);
+    let near = ao.visibility(&world, &point!(0.5,0.0001,0), &up, 0.0, &mut rng);
+    let far = ao.visibility(&world, &point!(5,0.0001,0), &up, 0.0, &mut rng);
+    assert!(near < far);
+}
+
+#[test]
+fn volume_occlusion_test() {
+    use crate::media::Volume;
+    use crate::shape::Shape;
+    use crate::point;
+    use crate::vector;
+
+    // smoke over the floor does not hide the sky
+    let mut world = World::new();
+    world.objects.push(Shape::plane());
+    let mut smoke = Shape::sphere();
+    smoke.transform = crate::matrix::Matrix4x4::translation(0.0, 1.0, 0.0);
+    smoke.material.volume = Some(Volume::new(0.0001, Color::BLACK));
+    world.objects.push(smoke);
+    let ao = AmbientOcclusion::new(64, 10.0);
+    let mut rng = Rng::new(5);
+    assert_eq!(ao.visibility(&world, &point!(0,0.0001,0), &vector!(0,1,0), 0.0, &mut rng), 1.0);
+
+    // looking straight through it there is nothing to occlude
+    let ray = Ray::new(point!(0,1,-5), vector!(0,0,1));
+    assert_eq!(ao.color_at(&world, &ray, &mut rng), Color::WHITE);
+}
@@ -0,0 +1,168 @@
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::sampling;
+use crate::sampling::Rng;
+use crate::world;
+use crate::world::World;
+use crate::light::Light;
+use crate::math::Float;
+use crate::tuple::Point;
+use crate::tuple::Vector;
+
+// bounces that always continue before russian roulette can end a path
+const MIN_BOUNCES : u32 = 3;
+
+/*
+    one Monte Carlo estimate of the light arriving along ray
+
+    every surface is treated as diffuse with the albedo of
+    Material::diffuse_albedo, there is no ambient term, indirect light
+    from other surfaces and the background takes its place
+
+    at each bounce the lights are sampled directly (next event
+    estimation) and a new direction is picked from a cosine weighted
+    hemisphere, after a few bounces paths are ended at random in
+    proportion to how little light they can still carry
+*/
+pub fn trace(world: &World, ray: &Ray, max_depth: u32, rng: &mut Rng) -> Color {
+    let mut radiance = Color::BLACK;
+    let mut throughput = Color::WHITE;
+    let mut ray = Ray::at_time(ray.origin.clone(), ray.direction.clone(), ray.time);
+
+    for depth in 0..max_depth {
+        let intersections = world::intersect(world, &ray);
+        let intersection = match world::surface_hit(&intersections) {
+            Some(intersection) => intersection,
+            None => {
+                let background = world.background.color_at(&ray.direction);
+                radiance = radiance.add(&throughput.hadamard(&background));
+                break;
+            }
+        };
+
+        let comps = world::prepare_computations(&ray, intersection);
+        let albedo = comps.object.material.diffuse_albedo(comps.object, &comps.point, comps.time);
+
+        // next event estimation, light intensity is the irradiance
+        // at normal incidence just like the phong diffuse term
+        for light in world.lights.iter() {
+            let direct = direct_light(world, light, &comps.over_point, &comps.normalv, ray.time);
+            radiance = radiance.add(&throughput.hadamard(&albedo).hadamard(&direct));
+        }
+
+        // the cosine and the pdf cancel, leaving just the albedo
+        throughput = throughput.hadamard(&albedo);
+
+        if depth + 1 >= MIN_BOUNCES {
+            let survive = throughput.red.max(throughput.green).max(throughput.blue).min(0.95);
+            if survive <= 0.0 || rng.next_float() >= survive {
+                break;
+            }
+            throughput = throughput.multiplyf(1.0 / survive);
+        }
+
+        let direction = sampling::cosine_weighted_hemisphere(&comps.normalv, rng);
+        ray = Ray::at_time(comps.over_point.clone(), direction, ray.time);
+    }
+    radiance
+}
+
+fn direct_light(world: &World, light: &Light, point: &Point, normal: &Vector, time: Float) -> Color {
+    let light_vector = light.position.subtract(point).normalize();
+    let cos = light_vector.dot(normal);
+    if cos <= 0.0 {
+        Color::BLACK
+    }
+    else {
+        light.intensity.hadamard(&world::shadow_transmittance(world, point, light, time)).multiplyf(cos)
+    }
+}
+
+#[test]
+fn trace_background_test() {
+    use crate::point;
+    use crate::vector;
+    use crate::rgb;
+
+    // an empty world only shows the background
+    let mut world = World::new();
+    world.background = crate::background::Background::Solid(rgb!(0.2,0.4,0.6));
+    let mut rng = Rng::new(1);
+    let ray = Ray::new(point!(0,0,0), vector!(0,0,1));
+    assert_eq!(trace(&world, &ray, 5, &mut rng), rgb!(0.2,0.4,0.6));
+}
+
+#[test]
+fn trace_sky_light_test() {
+    use crate::shape::Shape;
+    use crate::point;
+    use crate::vector;
+    use crate::rgb;
+
+    // a diffuse floor under a white sky reflects albedo of the
+    // sky no matter which way each bounce goes
+    let mut world = World::new();
+    world.background = crate::background::Background::Solid(Color::WHITE);
+    let mut floor = Shape::plane();
+    floor.material.color = rgb!(1,0.5,0.25);
+    floor.material.diffuse = 0.8;
+    world.objects.push(floor);
+    let mut rng = Rng::new(9);
+    let ray = Ray::new(point!(0,1,-1), vector!(0,-1,1).normalize());
+    for _i in 0..20 {
+        assert_eq!(trace(&world, &ray, 5, &mut rng), rgb!(0.8,0.4,0.2));
+    }
+
+    // with no bounces allowed nothing is gathered
+    assert_eq!(trace(&world, &ray, 0, &mut rng), Color::BLACK);
+}
+
+#[test]
+fn trace_direct_light_test() {
+    use crate::shape::Shape;
+    use crate::point;
+    use crate::vector;
+    use crate::rgb;
+
+    // a point light straight above a floor under a black sky
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(0,10,0), rgb!(1,1,1)));
+    let mut floor = Shape::plane();
+    floor.material.diffuse = 0.5;
+    world.objects.push(floor);
+    let mut rng = Rng::new(4);
+    let ray = Ray::new(point!(0,1,-1), vector!(0,-1,1).normalize());
+    assert_eq!(trace(&world, &ray, 5, &mut rng), rgb!(0.5,0.5,0.5));
+
+    // a blocker casts a shadow
+    let mut blocker = Shape::sphere();
+    blocker.transform = crate::matrix::Matrix4x4::translation(0.0, 5.0, 0.0);
+    world.objects.push(blocker);
+    let mut total = Color::BLACK;
+    for _i in 0..50 {
+        total = total.add(&trace(&world, &ray, 5, &mut rng));
+    }
+    // only the light bounced off the blocker's underside is left
+    let mean = total.multiplyf(1.0 / 50.0);
+    assert!(mean.red < 0.1);
+}
+
+#[test]
+fn trace_volume_test() {
+    use crate::media::Volume;
+    use crate::shape::Shape;
+    use crate::point;
+    use crate::vector;
+    use crate::rgb;
+
+    // thin smoke is not a surface, the sky shows through it
+    let mut world = World::new();
+    world.background = crate::background::Background::Solid(Color::WHITE);
+    let mut smoke = Shape::sphere();
+    smoke.material.color = rgb!(1,0,0);
+    smoke.material.volume = Some(Volume::new(0.0001, Color::BLACK));
+    world.objects.push(smoke);
+    let mut rng = Rng::new(2);
+    let ray = Ray::new(point!(0,0,-5), vector!(0,0,1));
+    assert_eq!(trace(&world, &ray, 4, &mut rng), Color::WHITE);
+}
@@ -0,0 +1,357 @@
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::math::Float;
+use crate::render::Integrator;
+use crate::render;
+use crate::render::Region;
+use crate::render::RenderSettings;
+use crate::sampling::Rng;
+use crate::world::World;
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+// first bytes of a checkpoint file, bump the digit when the layout changes
+const CHECKPOINT_MAGIC : &[u8; 8] = b"RTPROG2\n";
+
+/*
+    shared flag for stopping a render from another thread,
+    clones all refer to the same flag
+*/
+#[derive(Clone)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken {
+            cancelled: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> CancelToken {
+        CancelToken::new()
+    }
+}
+
+/*
+    the settings a pass depends on besides the scene, the integrator
+    as its kind and parameters, a checkpoint records them so it is
+    only carried on by a run that would add the same samples
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PassSettings {
+    seed: u64,
+    integrator: u32,
+    // max_depth for path tracing, the sample count for ambient occlusion
+    integrator_count: u32,
+    integrator_distance: f64
+}
+
+impl PassSettings {
+    fn new(settings: &RenderSettings) -> PassSettings {
+        let (integrator, integrator_count, integrator_distance) = match settings.integrator {
+            Integrator::Whitted => (0, 0, 0.0),
+            Integrator::PathTraced { max_depth } => (1, max_depth, 0.0),
+            Integrator::AmbientOcclusion(ref ao) => (2, ao.samples, ao.max_distance as f64)
+        };
+        PassSettings {
+            seed: settings.seed,
+            integrator,
+            integrator_count,
+            integrator_distance
+        }
+    }
+}
+
+/*
+    a render refined pass by pass, every pass adds one more jittered
+    sample to every pixel so the whole image sharpens together
+
+    settings.samples_per_pixel is ignored, the number of passes
+    takes its place
+*/
+pub struct ProgressiveRender {
+    pub width: u32,
+    pub height: u32,
+    // completed passes, each pixel has this many samples
+    pub passes: u32,
+    settings: PassSettings,
+    sums: Vec<Color>
+}
+
+impl ProgressiveRender {
+    pub fn new(width: u32, height: u32, settings: &RenderSettings) -> ProgressiveRender {
+        ProgressiveRender {
+            width,
+            height,
+            passes: 0,
+            settings: PassSettings::new(settings),
+            sums: vec![Color::BLACK; (width * height) as usize]
+        }
+    }
+
+    /*
+        whether run can add to these passes, a loaded checkpoint from
+        another image size, seed or integrator would mix samples of
+        different renders
+    */
+    pub fn resumes(&self, camera: &Camera, settings: &RenderSettings) -> bool {
+        self.width == camera.hsize
+            && self.height == camera.vsize
+            && self.settings == PassSettings::new(settings)
+    }
+
+    /*
+        adds passes until max_passes in total are done, the time
+        budget runs out or cancel is set and returns how many were
+        added, both are checked after every tile and a pass that is
+        cut short is thrown away so the image only ever holds
+        complete passes
+    */
+    pub fn run(
+        &mut self,
+        camera: &Camera,
+        world: &World,
+        settings: &RenderSettings,
+        max_passes: u32,
+        budget: Option<Duration>,
+        cancel: &CancelToken) -> u32 {
+
+        let start = Instant::now();
+        let stop = || cancel.is_cancelled() || budget.is_some_and(|budget| start.elapsed() >= budget);
+        let mut added = 0;
+        while self.passes < max_passes {
+            match self.render_pass(camera, world, settings, &stop) {
+                Some(pass) => {
+                    for (sum, sample) in self.sums.iter_mut().zip(pass.iter()) {
+                        *sum = sum.add(sample);
+                    }
+                    self.passes += 1;
+                    added += 1;
+                },
+                None => break
+            }
+        }
+        added
+    }
+
+    fn render_pass(&self, camera: &Camera, world: &World, settings: &RenderSettings, stop: &dyn Fn() -> bool) -> Option<Vec<Color>> {
+        // every pass gets its own random numbers and a resumed render carries on the sequence
+        let seed = settings.seed ^ (self.passes as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let mut pass = vec![Color::BLACK; self.sums.len()];
+        for tile in Region::new(0, 0, self.width, self.height).tiles(render::TILE_SIZE) {
+            if stop() {
+                return None;
+            }
+            render::render_tile(&tile, |x, y| {
+                let mut rng = Rng::for_pixel(seed, x, y);
+                pass[(y * self.width + x) as usize] = render::sample_pixel(camera, world, settings, x, y, true, &mut rng);
+            });
+        }
+        Some(pass)
+    }
+
+    // the mean of the passes so far, black before the first
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        if self.passes > 0 {
+            let scale = 1.0 / self.passes as Float;
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    canvas.set_pixel(x, y, &self.sums[(y * self.width + x) as usize].multiplyf(scale));
+                }
+            }
+        }
+        canvas
+    }
+
+    /*
+        the accumulated samples as bytes, the magic, then width,
+        height and passes as little endian u32, the seed as a u64,
+        the integrator kind and count as u32 and its distance as f64,
+        then each pixel's summed red, green and blue as little endian
+        f64 row by row
+    */
+    pub fn to_checkpoint(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(44 + self.sums.len() * 24);
+        data.extend_from_slice(CHECKPOINT_MAGIC);
+        for value in [self.width, self.height, self.passes].iter() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&self.settings.seed.to_le_bytes());
+        data.extend_from_slice(&self.settings.integrator.to_le_bytes());
+        data.extend_from_slice(&self.settings.integrator_count.to_le_bytes());
+        data.extend_from_slice(&self.settings.integrator_distance.to_le_bytes());
+        for sum in self.sums.iter() {
+            for channel in [sum.red, sum.green, sum.blue].iter() {
+                data.extend_from_slice(&(*channel as f64).to_le_bytes());
+            }
+        }
+        data
+    }
+
+    pub fn from_checkpoint(data: &[u8]) -> Result<ProgressiveRender, String> {
+        let mut reader = data;
+        let magic: [u8; 8] = read_bytes(&mut reader)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err("not a checkpoint".to_string());
+        }
+        let mut read_u32 = || -> Result<u32, String> {
+            read_bytes(&mut reader).map(u32::from_le_bytes)
+        };
+        let width = read_u32()?;
+        let height = read_u32()?;
+        let passes = read_u32()?;
+        let settings = PassSettings {
+            seed: read_bytes(&mut reader).map(u64::from_le_bytes)?,
+            integrator: read_bytes(&mut reader).map(u32::from_le_bytes)?,
+            integrator_count: read_bytes(&mut reader).map(u32::from_le_bytes)?,
+            integrator_distance: read_bytes(&mut reader).map(f64::from_le_bytes)?
+        };
+        let pixels = width as usize * height as usize;
+        if reader.len() != pixels * 24 {
+            return Err(format!("checkpoint should hold {} pixels", pixels));
+        }
+        let channel = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&reader[i * 8..i * 8 + 8]);
+            f64::from_le_bytes(bytes) as Float
+        };
+        let sums = (0..pixels)
+            .map(|i| Color::rgb(channel(i * 3), channel(i * 3 + 1), channel(i * 3 + 2)))
+            .collect();
+        Ok(ProgressiveRender {
+            width,
+            height,
+            passes,
+            settings,
+            sums
+        })
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_checkpoint())
+    }
+
+    pub fn load(path: &std::path::Path) -> std::io::Result<ProgressiveRender> {
+        let data = std::fs::read(path)?;
+        ProgressiveRender::from_checkpoint(&data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+// the next N bytes of reader, which moves past them
+fn read_bytes<const N: usize>(reader: &mut &[u8]) -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes).map_err(|_| "checkpoint is too short".to_string())?;
+    Ok(bytes)
+}
+
+#[test]
+fn progressive_render_test() {
+    let (camera, world) = render::test_scene(8, 6);
+    let settings = RenderSettings::new();
+    let cancel = CancelToken::new();
+
+    let mut progressive = ProgressiveRender::new(8, 6, &settings);
+    assert_eq!(progressive.to_canvas().get_pixel(4, 3), Color::BLACK);
+    assert_eq!(progressive.run(&camera, &world, &settings, 4, None, &cancel), 4);
+    assert_eq!(progressive.passes, 4);
+    assert!(progressive.to_canvas().get_pixel(4, 3).red > 0.0);
+
+    // already done
+    assert_eq!(progressive.run(&camera, &world, &settings, 4, None, &cancel), 0);
+
+    // stopping in two passes and carrying on gives the same image as going straight through
+    let mut split = ProgressiveRender::new(8, 6, &settings);
+    split.run(&camera, &world, &settings, 2, None, &cancel);
+    split.run(&camera, &world, &settings, 4, None, &cancel);
+    for y in 0..6 {
+        for x in 0..8 {
+            assert_eq!(split.to_canvas().get_pixel(x, y), progressive.to_canvas().get_pixel(x, y));
+        }
+    }
+}
+
+#[test]
+fn progressive_render_stop_test() {
+    let (camera, world) = render::test_scene(8, 6);
+    let settings = RenderSettings::new();
+
+    // cancelled before it starts, nothing is added
+    let cancel = CancelToken::new();
+    cancel.clone().cancel();
+    assert!(cancel.is_cancelled());
+    let mut progressive = ProgressiveRender::new(8, 6, &settings);
+    assert_eq!(progressive.run(&camera, &world, &settings, 100, None, &cancel), 0);
+    assert_eq!(progressive.passes, 0);
+
+    // as is a spent time budget
+    let cancel = CancelToken::new();
+    assert_eq!(progressive.run(&camera, &world, &settings, 100, Some(Duration::from_secs(0)), &cancel), 0);
+
+    // a cancel from another thread stops an endless render
+    let stopper = cancel.clone();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        stopper.cancel();
+    });
+    let added = progressive.run(&camera, &world, &settings, u32::MAX, None, &cancel);
+    handle.join().unwrap();
+    assert!(added > 0 && added < u32::MAX);
+    assert_eq!(progressive.passes, added);
+}
+
+#[test]
+fn checkpoint_test() {
+    let (camera, world) = render::test_scene(8, 6);
+    let settings = RenderSettings::new();
+    let cancel = CancelToken::new();
+
+    let mut progressive = ProgressiveRender::new(8, 6, &settings);
+    progressive.run(&camera, &world, &settings, 3, None, &cancel);
+    let data = progressive.to_checkpoint();
+    let mut resumed = ProgressiveRender::from_checkpoint(&data).unwrap();
+    assert_eq!(resumed.width, 8);
+    assert_eq!(resumed.height, 6);
+    assert_eq!(resumed.passes, 3);
+
+    // resuming continues exactly where the original would have
+    resumed.run(&camera, &world, &settings, 5, None, &cancel);
+    progressive.run(&camera, &world, &settings, 5, None, &cancel);
+    assert_eq!(resumed.to_canvas().get_pixel(4, 3), progressive.to_canvas().get_pixel(4, 3));
+
+    // only a run with the same size, seed and integrator carries it on
+    assert!(resumed.resumes(&camera, &settings));
+    let mut other_seed = RenderSettings::new();
+    other_seed.seed = 7;
+    assert!(!resumed.resumes(&camera, &other_seed));
+    assert!(!resumed.resumes(&camera, &RenderSettings::path_traced(1, 5)));
+    let mut bigger = Camera::new(16, 12, crate::math::consts::PI / 2.0);
+    bigger.transform = camera.transform.clone();
+    assert!(!resumed.resumes(&bigger, &settings));
+    let path_traced = RenderSettings::path_traced(1, 5);
+    let checkpoint = ProgressiveRender::new(8, 6, &path_traced).to_checkpoint();
+    let loaded = ProgressiveRender::from_checkpoint(&checkpoint).unwrap();
+    assert!(loaded.resumes(&camera, &path_traced));
+    assert!(!loaded.resumes(&camera, &RenderSettings::path_traced(1, 6)));
+
+    assert!(ProgressiveRender::from_checkpoint(b"P3\n").is_err());
+    assert!(ProgressiveRender::from_checkpoint(&data[..data.len() - 1]).is_err());
+}
@@ -0,0 +1,179 @@
+use crate::math::fequal;
+use crate::math::Float;
+use crate::matrix::Matrix4x4;
+use crate::tuple::Vector;
+use crate::vector;
+
+/*
+    unit quaternions represent rotations without the gimbal lock
+    you get when composing rotation_x, rotation_y and rotation_z
+*/
+#[derive(Debug, Clone)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float
+}
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub const IDENTITY : Quaternion = Quaternion { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+
+    // rotation of r radians around axis, the axis does not need to be normalized
+    pub fn from_axis_angle(axis: &Vector, r: Float) -> Quaternion {
+        let axis = axis.normalize();
+        let half = r / 2.0;
+        let sin_half = half.sin();
+        Quaternion {
+            w: half.cos(),
+            x: axis.x * sin_half,
+            y: axis.y * sin_half,
+            z: axis.z * sin_half
+        }
+    }
+
+    // rotation part of the matrix, any scale in the matrix must be removed first
+    pub fn from_matrix(m: &Matrix4x4) -> Quaternion {
+        m.to_quaternion()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4x4 {
+        Matrix4x4::rotation_quaternion(self)
+    }
+
+    pub fn multiply(&self, rhs: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w
+        }
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn dot(&self, rhs: &Quaternion) -> Float {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn magnitude(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        let mag = self.magnitude();
+        Quaternion::new(self.w / mag, self.x / mag, self.y / mag, self.z / mag)
+    }
+
+    pub fn rotate(&self, v: &Vector) -> Vector {
+        let p = Quaternion::new(0.0, v.x, v.y, v.z);
+        let r = self.multiply(&p).multiply(&self.conjugate());
+        vector!(r.x, r.y, r.z)
+    }
+
+    /*
+        spherical linear interpolation, t = 0 is self and t = 1 is other
+
+        always takes the shortest path around the sphere and falls
+        back to a normalized lerp when the rotations are nearly equal
+    */
+    pub fn slerp(&self, other: &Quaternion, t: Float) -> Quaternion {
+        let mut other = other.clone();
+        let mut cos_theta = self.dot(&other);
+        if cos_theta < 0.0 {
+            other = Quaternion::new(-other.w, -other.x, -other.y, -other.z);
+            cos_theta = -cos_theta;
+        }
+
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        }
+        else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+
+        Quaternion::new(
+            self.w * a + other.w * b,
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b
+        ).normalize()
+    }
+}
+
+/*
+    q and -q are the same rotation so both compare equal
+*/
+impl PartialEq for Quaternion {
+    fn eq(&self, other: &Self) -> bool {
+        let same = fequal(self.w, other.w) && fequal(self.x, other.x) &&
+            fequal(self.y, other.y) && fequal(self.z, other.z);
+        let negated = fequal(self.w, -other.w) && fequal(self.x, -other.x) &&
+            fequal(self.y, -other.y) && fequal(self.z, -other.z);
+        same || negated
+    }
+}
+
+#[test]
+fn from_axis_angle_test() {
+    let half_pi = crate::math::consts::PI / 2.0;
+    let q = Quaternion::from_axis_angle(&vector!(1,0,0), half_pi);
+    assert_eq!(q.to_matrix(), Matrix4x4::rotation_x(half_pi));
+    let q = Quaternion::from_axis_angle(&vector!(0,2,0), half_pi);
+    assert_eq!(q.to_matrix(), Matrix4x4::rotation_y(half_pi));
+    let q = Quaternion::from_axis_angle(&vector!(0,0,1), half_pi);
+    assert_eq!(q.to_matrix(), Matrix4x4::rotation_z(half_pi));
+    assert_eq!(q.rotate(&vector!(0,1,0)), vector!(-1,0,0));
+}
+
+#[test]
+fn multiply_test() {
+    // two quarter turns make a half turn
+    let quarter = Quaternion::from_axis_angle(&vector!(0,1,0), crate::math::consts::PI / 2.0);
+    let half = Quaternion::from_axis_angle(&vector!(0,1,0), crate::math::consts::PI);
+    assert_eq!(quarter.multiply(&quarter), half);
+
+    // composing quaternions matches composing matrices
+    let a = Quaternion::from_axis_angle(&vector!(1,0,0), 0.3);
+    let b = Quaternion::from_axis_angle(&vector!(0,0,1), 1.1);
+    let m = Matrix4x4::rotation_x(0.3).multiply(&Matrix4x4::rotation_z(1.1));
+    assert_eq!(a.multiply(&b).to_matrix(), m);
+}
+
+#[test]
+fn from_matrix_test() {
+    let m = Matrix4x4::rotation_y(0.5).multiply(&Matrix4x4::rotation_x(2.5));
+    let q = Quaternion::from_matrix(&m);
+    assert_eq!(q.to_matrix(), m);
+    assert_eq!(Quaternion::from_matrix(&crate::matrix::MATRIX_4X4_IDENTITY), Quaternion::IDENTITY);
+}
+
+#[test]
+fn slerp_test() {
+    use crate::point;
+
+    let axis = vector!(0,0,1);
+    let a = Quaternion::IDENTITY;
+    let b = Quaternion::from_axis_angle(&axis, crate::math::consts::PI / 2.0);
+    assert_eq!(a.slerp(&b, 0.0), a);
+    assert_eq!(a.slerp(&b, 1.0), b);
+    assert_eq!(a.slerp(&b, 0.5), Quaternion::from_axis_angle(&axis, crate::math::consts::PI / 4.0));
+
+    // nearly identical rotations still interpolate to a unit quaternion
+    let c = Quaternion::from_axis_angle(&axis, 0.001);
+    assert!(fequal(a.slerp(&c, 0.5).magnitude(), 1.0));
+
+    // takes the short way around
+    let d = Quaternion::from_axis_angle(&axis, crate::math::consts::PI * 1.5);
+    let p = a.slerp(&d, 0.5).to_matrix().multiply_tuple(&point!(1,0,0));
+    let frac = crate::math::consts::FRAC_1_SQRT_2;
+    assert_eq!(p, point!(frac, -frac, 0));
+}
@@ -6,28 +6,37 @@ use crate::vector;
 use crate::matrix;
 use crate::matrix::Matrix4x4;
 use crate::shape::Shape;
+use crate::math::Float;
 
 pub struct Ray {
     pub origin: Point,
-    pub direction: Vector
+    pub direction: Vector,
+    // when during the shutter the ray is traced, see Shape::set_end_transform
+    pub time: Float
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Ray {
+        Ray::at_time(origin, direction, 0.0)
+    }
+
+    pub fn at_time(origin: Point, direction: Vector, time: Float) -> Ray {
         Ray {
             origin,
-            direction
+            direction,
+            time
         }
     }
 
-    pub fn position(&self, t: f32) -> Point {
+    pub fn position(&self, t: Float) -> Point {
         self.origin.add(&self.direction.multiplyf(t))
     }
 
     pub fn transform(&self, m: &Matrix4x4) -> Ray {
-        Ray::new(
+        Ray::at_time(
             m.multiply_tuple(&self.origin),
-            m.multiply_tuple(&self.direction)
+            m.multiply_tuple(&self.direction),
+            self.time
         )
     }
 }
@@ -0,0 +1,471 @@
+use crate::camera::Camera;
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::math::Float;
+use crate::occlusion::AmbientOcclusion;
+use crate::path_tracer;
+use crate::sampling::Rng;
+use crate::stats;
+use crate::world::World;
+use std::time::Instant;
+
+pub enum Integrator {
+    // World::color_at, phong lighting with hard shadows
+    Whitted,
+    // path_tracer::trace, indirect light by Monte Carlo sampling
+    PathTraced { max_depth: u32 },
+    // a grey image of just the ambient occlusion, no lighting
+    AmbientOcclusion(AmbientOcclusion)
+}
+
+pub struct RenderSettings {
+    pub integrator: Integrator,
+    pub samples_per_pixel: u32,
+    // every random choice in a render follows from this
+    pub seed: u64,
+    // more samples where the first samples_per_pixel disagree, see AdaptiveSampling
+    pub adaptive: Option<AdaptiveSampling>
+}
+
+/*
+    keeps sampling a pixel past samples_per_pixel, up to max_samples,
+    while the standard error of its mean in any channel is above
+    max_error, at least two samples are taken to have a variance
+*/
+pub struct AdaptiveSampling {
+    pub max_samples: u32,
+    pub max_error: Float
+}
+
+impl AdaptiveSampling {
+    pub fn new(max_samples: u32, max_error: Float) -> AdaptiveSampling {
+        AdaptiveSampling {
+            max_samples,
+            max_error
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn new() -> RenderSettings {
+        RenderSettings {
+            integrator: Integrator::Whitted,
+            samples_per_pixel: 1,
+            seed: 0,
+            adaptive: None
+        }
+    }
+
+    pub fn path_traced(samples_per_pixel: u32, max_depth: u32) -> RenderSettings {
+        RenderSettings {
+            integrator: Integrator::PathTraced { max_depth },
+            samples_per_pixel,
+            seed: 0,
+            adaptive: None
+        }
+    }
+
+    // the most samples a pixel can get
+    pub fn max_samples(&self) -> u32 {
+        let samples = self.samples_per_pixel.max(1);
+        match self.adaptive {
+            Some(ref adaptive) => samples.max(2).max(adaptive.max_samples),
+            None => samples
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings::new()
+    }
+}
+
+/*
+    running mean and variance of a pixel's samples per channel,
+    updated one sample at a time with Welford's algorithm
+*/
+pub struct PixelEstimate {
+    pub samples: u32,
+    pub mean: Color,
+    // sum of squared differences from the mean
+    m2: Color
+}
+
+impl PixelEstimate {
+    pub fn new() -> PixelEstimate {
+        PixelEstimate {
+            samples: 0,
+            mean: Color::BLACK,
+            m2: Color::BLACK
+        }
+    }
+
+    pub fn add(&mut self, sample: &Color) {
+        self.samples += 1;
+        let delta = sample.subtract(&self.mean);
+        self.mean = self.mean.add(&delta.multiplyf(1.0 / self.samples as Float));
+        self.m2 = self.m2.add(&delta.hadamard(&sample.subtract(&self.mean)));
+    }
+
+    // sample variance, 0 until there are two samples
+    pub fn variance(&self) -> Color {
+        if self.samples < 2 {
+            return Color::BLACK;
+        }
+        self.m2.multiplyf(1.0 / (self.samples - 1) as Float)
+    }
+
+    // the largest standard error of the mean over the channels
+    pub fn error(&self) -> Float {
+        let variance = self.variance();
+        let largest = variance.red.max(variance.green).max(variance.blue);
+        (largest / self.samples.max(1) as Float).sqrt()
+    }
+}
+
+impl Default for PixelEstimate {
+    fn default() -> PixelEstimate {
+        PixelEstimate::new()
+    }
+}
+
+// render_region works through a region in tiles of this size, timing each
+pub(crate) const TILE_SIZE : u32 = 16;
+
+// a rectangle of pixels in the camera's frame, x, y is its top left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height
+        }
+    }
+
+    pub fn full(camera: &Camera) -> Region {
+        Region::new(0, 0, camera.hsize, camera.vsize)
+    }
+
+    // the region cut into size by size squares, narrower along the right and bottom edges,
+    // a size of 0 is taken as 1
+    pub fn tiles(&self, size: u32) -> Vec<Region> {
+        let size = size.max(1);
+        let mut tiles = Vec::new();
+        for y in (self.y..self.y + self.height).step_by(size as usize) {
+            for x in (self.x..self.x + self.width).step_by(size as usize) {
+                let width = size.min(self.x + self.width - x);
+                let height = size.min(self.y + self.height - y);
+                tiles.push(Region::new(x, y, width, height));
+            }
+        }
+        tiles
+    }
+
+    // the part of the region inside the camera's frame, possibly empty
+    pub fn clip(&self, camera: &Camera) -> Region {
+        let x = self.x.min(camera.hsize);
+        let y = self.y.min(camera.vsize);
+        Region::new(
+            x,
+            y,
+            self.width.min(camera.hsize - x),
+            self.height.min(camera.vsize - y))
+    }
+}
+
+pub fn render(camera: &Camera, world: &World, settings: &RenderSettings, canvas: &mut Canvas) {
+    render_region(camera, world, settings, &Region::full(camera), canvas);
+}
+
+/*
+    renders only the pixels in region into a canvas the size of
+    the whole frame, every other pixel is left as it was so a detail
+    can be re-rendered over an earlier full render
+*/
+pub fn render_region(camera: &Camera, world: &World, settings: &RenderSettings, region: &Region, canvas: &mut Canvas) {
+    render_tiles(&region.clip(camera), |x, y| {
+        let color = render_pixel(camera, world, settings, x, y);
+        canvas.set_pixel(x, y, &color);
+    });
+}
+
+/*
+    calls render_pixel for every pixel of region a tile at a time,
+    every render loops through here or render_tile so stats has the
+    time of each tile whichever way the image was made
+*/
+pub(crate) fn render_tiles(region: &Region, mut render_pixel: impl FnMut(u32, u32)) {
+    for tile in region.tiles(TILE_SIZE) {
+        render_tile(&tile, &mut render_pixel);
+    }
+}
+
+pub(crate) fn render_tile(tile: &Region, mut render_pixel: impl FnMut(u32, u32)) {
+    let start = Instant::now();
+    for y in tile.y..tile.y + tile.height {
+        for x in tile.x..tile.x + tile.width {
+            render_pixel(x, y);
+        }
+    }
+    stats::record_tile(tile, start.elapsed());
+}
+
+/*
+    renders the pixels in region into a canvas of just that size,
+    they come out the same as in a full render
+*/
+pub fn render_crop(camera: &Camera, world: &World, settings: &RenderSettings, region: &Region) -> Canvas {
+    let region = region.clip(camera);
+    let mut canvas = Canvas::new(region.width, region.height);
+    render_tiles(&region, |x, y| {
+        let color = render_pixel(camera, world, settings, x, y);
+        canvas.set_pixel(x - region.x, y - region.y, &color);
+    });
+    canvas
+}
+
+/*
+    the mean of samples_per_pixel samples, a single sample goes
+    through the pixel center in the middle of the shutter interval,
+    more are spread at random over the pixel and the interval
+*/
+pub fn render_pixel(camera: &Camera, world: &World, settings: &RenderSettings, x: u32, y: u32) -> Color {
+    estimate_pixel(camera, world, settings, x, y).mean
+}
+
+// render_pixel along with how many samples it took and how much they varied
+pub fn estimate_pixel(camera: &Camera, world: &World, settings: &RenderSettings, x: u32, y: u32) -> PixelEstimate {
+    let mut rng = Rng::for_pixel(settings.seed, x, y);
+    let max_samples = settings.max_samples();
+    let min_samples = match settings.adaptive {
+        Some(_) => settings.samples_per_pixel.max(2),
+        None => max_samples
+    };
+    let max_error = settings.adaptive.as_ref().map_or(0.0, |adaptive| adaptive.max_error);
+    let mut estimate = PixelEstimate::new();
+    while estimate.samples < max_samples {
+        if estimate.samples >= min_samples && estimate.error() <= max_error {
+            break;
+        }
+        estimate.add(&sample_pixel(camera, world, settings, x, y, max_samples > 1, &mut rng));
+    }
+    estimate
+}
+
+/*
+    render::render that also returns a heat map of the samples
+    each pixel took, black for the fewest possible through red
+    and yellow to white for max_samples
+*/
+pub fn render_with_heatmap(camera: &Camera, world: &World, settings: &RenderSettings, canvas: &mut Canvas) -> Canvas {
+    let mut heatmap = Canvas::new(camera.hsize, camera.vsize);
+    let fewest = match settings.adaptive {
+        Some(_) => settings.samples_per_pixel.max(2),
+        None => settings.max_samples()
+    };
+    let range = (settings.max_samples() - fewest).max(1) as Float;
+    render_tiles(&Region::full(camera), |x, y| {
+        let estimate = estimate_pixel(camera, world, settings, x, y);
+        canvas.set_pixel(x, y, &estimate.mean);
+        let t = 3.0 * (estimate.samples - fewest) as Float / range;
+        let heat = Color::rgb(t.clamp(0.0, 1.0), (t - 1.0).clamp(0.0, 1.0), (t - 2.0).clamp(0.0, 1.0));
+        heatmap.set_pixel(x, y, &heat);
+    });
+    heatmap
+}
+
+/*
+    one sample of pixel x, y with the settings' integrator, jittered
+    over the pixel and shutter interval or through the pixel center
+*/
+pub(crate) fn sample_pixel(camera: &Camera, world: &World, settings: &RenderSettings, x: u32, y: u32, jitter: bool, rng: &mut Rng) -> Color {
+    let ray = if jitter {
+        let mut ray = camera.ray_for_pixel_offset(x, y, rng.next_float(), rng.next_float());
+        ray.time = camera.shutter_time(rng.next_float());
+        ray
+    }
+    else {
+        camera.ray_for_pixel(x, y)
+    };
+    stats::count_primary_ray();
+    match settings.integrator {
+        Integrator::Whitted => world.color_at(&ray),
+        Integrator::PathTraced { max_depth } => path_tracer::trace(world, &ray, max_depth, rng),
+        Integrator::AmbientOcclusion(ref ao) => ao.color_at(world, &ray, rng)
+    }
+}
+
+// a point light and a unit sphere seen from 5 units down -z
+#[cfg(test)]
+pub(crate) fn test_scene(width: u32, height: u32) -> (Camera, World) {
+    use crate::shape::Shape;
+    use crate::light::Light;
+    use crate::point;
+    use crate::rgb;
+
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-10,10,-10), rgb!(1,1,1)));
+    world.objects.push(Shape::sphere());
+    let mut camera = Camera::new(width, height, crate::math::consts::PI / 2.0);
+    camera.transform = crate::matrix::Matrix4x4::view_transform(&point!(0,0,-5), &point!(0,0,0), &crate::vector!(0,1,0));
+    (camera, world)
+}
+
+#[test]
+fn render_test() {
+    use crate::shape::Shape;
+    use crate::light::Light;
+    use crate::point;
+    use crate::rgb;
+
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-10,10,-10), rgb!(1,1,1)));
+    let mut sphere = Shape::sphere();
+    sphere.material.color = rgb!(0.8,1,0.6);
+    world.objects.push(sphere);
+    let mut camera = Camera::new(11, 11, crate::math::consts::PI / 2.0);
+    camera.transform = crate::matrix::Matrix4x4::view_transform(&point!(0,0,-5), &point!(0,0,0), &crate::vector!(0,1,0));
+
+    // whitted with one sample matches tracing the center ray
+    let mut canvas = Canvas::new(11, 11);
+    render(&camera, &world, &RenderSettings::new(), &mut canvas);
+    assert_eq!(canvas.get_pixel(5, 5), world.color_at(&camera.ray_for_pixel(5, 5)));
+    assert_eq!(canvas.get_pixel(0, 0), Color::BLACK);
+
+    // path tracing is repeatable for the same seed
+    let settings = RenderSettings::path_traced(4, 4);
+    let a = render_pixel(&camera, &world, &settings, 5, 5);
+    let b = render_pixel(&camera, &world, &settings, 5, 5);
+    assert_eq!(a, b);
+    assert!(a.green > 0.0);
+}
+
+#[test]
+fn motion_blur_test() {
+    use crate::shape::Shape;
+    use crate::point;
+    use crate::rgb;
+
+    // a white sphere sweeping across a black background
+    let mut world = World::new();
+    world.lights.push(crate::light::Light::point_light(point!(0,0,-10), rgb!(1,1,1)));
+    let mut sphere = Shape::sphere();
+    sphere.material.ambient = 1.0;
+    sphere.material.diffuse = 0.0;
+    sphere.material.specular = 0.0;
+    sphere.transform = crate::matrix::Matrix4x4::translation(-3.0, 0.0, 0.0);
+    sphere.set_end_transform(Some(crate::matrix::Matrix4x4::translation(3.0, 0.0, 0.0)));
+    world.objects.push(sphere);
+    let mut camera = Camera::new(11, 11, crate::math::consts::PI / 2.0);
+    camera.transform = crate::matrix::Matrix4x4::view_transform(&point!(0,0,-5), &point!(0,0,0), &crate::vector!(0,1,0));
+
+    // with the shutter closed the sphere sits at its start
+    let mut settings = RenderSettings::new();
+    settings.samples_per_pixel = 64;
+    assert_eq!(render_pixel(&camera, &world, &settings, 5, 5), Color::BLACK);
+
+    // with it open, it covers the middle pixel for about a third of the time
+    camera.shutter_close = 1.0;
+    let color = render_pixel(&camera, &world, &settings, 5, 5);
+    assert!(color.red > 0.2 && color.red < 0.5);
+
+    // a single sample is taken halfway through the shutter
+    assert_eq!(render_pixel(&camera, &world, &RenderSettings::new(), 5, 5), rgb!(1,1,1));
+}
+
+#[test]
+fn render_region_test() {
+    use crate::rgb;
+
+    let (camera, world) = test_scene(11, 11);
+    let settings = RenderSettings::new();
+    let mut full = Canvas::new(11, 11);
+    render(&camera, &world, &settings, &mut full);
+
+    // only the region is drawn over what was already there
+    let marker = rgb!(0.25,0.5,0.75);
+    let mut canvas = Canvas::new(11, 11);
+    for y in 0..11 {
+        for x in 0..11 {
+            canvas.set_pixel(x, y, &marker);
+        }
+    }
+    let region = Region::new(4, 3, 3, 5);
+    render_region(&camera, &world, &settings, &region, &mut canvas);
+    for y in 0..11 {
+        for x in 0..11 {
+            let inside = (4..7).contains(&x) && (3..8).contains(&y);
+            let expected = if inside { full.get_pixel(x, y) } else { marker.clone() };
+            assert_eq!(canvas.get_pixel(x, y), expected);
+        }
+    }
+
+    // a crop holds the same pixels
+    let crop = render_crop(&camera, &world, &settings, &region);
+    for y in 0..5 {
+        for x in 0..3 {
+            assert_eq!(crop.get_pixel(x, y), full.get_pixel(x + 4, y + 3));
+        }
+    }
+
+    // regions hanging off the frame are clipped to it
+    assert_eq!(Region::new(9, 10, 5, 5).clip(&camera), Region::new(9, 10, 2, 1));
+    assert_eq!(Region::new(20, 0, 5, 5).clip(&camera), Region::new(11, 0, 0, 5));
+    assert_eq!(Region::new(1, 2, 5, 3).tiles(4), vec![Region::new(1, 2, 4, 3), Region::new(5, 2, 1, 3)]);
+    assert!(Region::new(11, 0, 0, 5).tiles(4).is_empty());
+    assert_eq!(Region::new(1, 2, 2, 1).tiles(0), vec![Region::new(1, 2, 1, 1), Region::new(2, 2, 1, 1)]);
+    let crop = render_crop(&camera, &world, &settings, &Region::new(9, 10, 5, 5));
+    assert_eq!((crop.width(), crop.height()), (2, 1));
+}
+
+#[test]
+fn pixel_estimate_test() {
+    use crate::rgb;
+
+    let mut estimate = PixelEstimate::new();
+    assert_eq!(estimate.error(), 0.0);
+    for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].iter() {
+        estimate.add(&rgb!(*value, 1, 0));
+    }
+    assert_eq!(estimate.samples, 8);
+    assert_eq!(estimate.mean, rgb!(5,1,0));
+    // 32 / 7 in red, nothing in the others
+    assert_eq!(estimate.variance(), rgb!(32.0 / 7.0, 0, 0));
+    assert!(crate::math::fequal(estimate.error(), (4.0 as Float / 7.0).sqrt()));
+}
+
+#[test]
+fn adaptive_sampling_test() {
+    let (camera, world) = test_scene(11, 11);
+    let mut settings = RenderSettings::new();
+    settings.samples_per_pixel = 4;
+    settings.adaptive = Some(AdaptiveSampling::new(64, 0.01));
+
+    // empty background agrees with itself, the sphere's edge does not
+    let background = estimate_pixel(&camera, &world, &settings, 0, 0);
+    assert_eq!(background.samples, 4);
+    assert_eq!(background.mean, Color::BLACK);
+    let edge = estimate_pixel(&camera, &world, &settings, 4, 4);
+    assert!(edge.samples > 4 && edge.samples <= 64);
+
+    // without adaptive sampling every pixel gets the same
+    settings.adaptive = None;
+    assert_eq!(estimate_pixel(&camera, &world, &settings, 4, 4).samples, 4);
+
+    settings.adaptive = Some(AdaptiveSampling::new(64, 0.01));
+    let mut canvas = Canvas::new(11, 11);
+    let heatmap = render_with_heatmap(&camera, &world, &settings, &mut canvas);
+    assert_eq!(heatmap.get_pixel(0, 0), Color::BLACK);
+    assert!(heatmap.get_pixel(4, 4).red > 0.0);
+    assert_eq!(canvas.get_pixel(4, 4), edge.mean);
+}
@@ -0,0 +1,136 @@
+use crate::math::Float;
+use crate::math::consts;
+use crate::tuple::Point;
+use crate::tuple::Vector;
+use crate::vector;
+
+/*
+    small deterministic random number generator (xorshift64*)
+
+    the stochastic parts of the renderer seed one of these per
+    pixel so a render is repeatable no matter the pixel order
+*/
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // run the seed through splitmix64 so nearby seeds give unrelated sequences
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // xorshift gets stuck on zero
+        if z == 0 {
+            z = 1;
+        }
+        Rng { state: z }
+    }
+
+    // a generator for one pixel of one render
+    pub fn for_pixel(seed: u64, x: u32, y: u32) -> Rng {
+        Rng::new(seed ^ ((x as u64) << 32 | y as u64).wrapping_mul(0x2545_F491_4F6C_DD1D))
+    }
+
+    // a generator that depends only on where in the scene it is used
+    pub fn for_point(point: &Point) -> Rng {
+        let mut seed = 0u64;
+        for c in [point.x, point.y, point.z].iter() {
+            seed = (seed ^ c.to_bits() as u64).wrapping_mul(0x100_0000_01B3);
+        }
+        Rng::new(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // uniform in 0 to 1, never 1, 24 bits so it is exact in f32 too
+    pub fn next_float(&mut self) -> Float {
+        (self.next_u64() >> 40) as Float / 16_777_216.0
+    }
+}
+
+/*
+    two unit vectors perpendicular to n and to each other
+*/
+pub fn orthonormal_basis(n: &Vector) -> (Vector, Vector) {
+    let helper = if n.x.abs() > 0.9 { vector!(0,1,0) } else { vector!(1,0,0) };
+    let tangent = helper.cross(n).normalize();
+    let bitangent = n.cross(&tangent);
+    (tangent, bitangent)
+}
+
+/*
+    random direction in the hemisphere around normal with
+    probability proportional to the cosine from the normal,
+    which cancels the cosine term of a diffuse surface
+*/
+pub fn cosine_weighted_hemisphere(normal: &Vector, rng: &mut Rng) -> Vector {
+    let r1 = rng.next_float();
+    let r2 = rng.next_float();
+    let phi = 2.0 * consts::PI * r1;
+    let radius = r2.sqrt();
+    let x = radius * phi.cos();
+    let y = radius * phi.sin();
+    let z = (1.0 - r2).max(0.0).sqrt();
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    tangent.multiplyf(x)
+        .add(&bitangent.multiplyf(y))
+        .add(&normal.multiplyf(z))
+        .normalize()
+}
+
+#[test]
+fn rng_test() {
+    let mut a = Rng::new(7);
+    let mut b = Rng::new(7);
+    let mut c = Rng::new(8);
+    let mut differs = false;
+    let mut sum = 0.0;
+    for _i in 0..1000 {
+        let f = a.next_float();
+        assert!((0.0..1.0).contains(&f));
+        assert_eq!(f, b.next_float());
+        differs = differs || f != c.next_float();
+        sum += f;
+    }
+    assert!(differs);
+    assert!((sum / 1000.0 - 0.5).abs() < 0.05);
+
+    // a zero seed still produces numbers
+    let mut z = Rng::new(0);
+    assert!(z.next_u64() != z.next_u64());
+}
+
+#[test]
+fn orthonormal_basis_test() {
+    for n in [vector!(0,1,0), vector!(1,0,0), vector!(1,2,3).normalize()].iter() {
+        let (t, b) = orthonormal_basis(n);
+        assert!(crate::math::fequal(t.magnitude(), 1.0));
+        assert!(crate::math::fequal(b.magnitude(), 1.0));
+        assert!(crate::math::fequal(t.dot(n), 0.0));
+        assert!(crate::math::fequal(b.dot(n), 0.0));
+        assert!(crate::math::fequal(t.dot(&b), 0.0));
+    }
+}
+
+#[test]
+fn cosine_weighted_hemisphere_test() {
+    let mut rng = Rng::new(3);
+    let normal = vector!(0,0,-1);
+    let mut mean_cos = 0.0;
+    for _i in 0..4000 {
+        let d = cosine_weighted_hemisphere(&normal, &mut rng);
+        assert!(crate::math::fequal(d.magnitude(), 1.0));
+        let cos = d.dot(&normal);
+        assert!(cos >= 0.0);
+        mean_cos += cos;
+    }
+    // the mean cosine of a cosine weighted hemisphere is 2/3
+    assert!((mean_cos / 4000.0 - 2.0 / 3.0).abs() < 0.02);
+}
@@ -3,21 +3,46 @@ use crate::tuple::Vector;
 use crate::point;
 use crate::vector;
 use crate::matrix;
+use crate::matrix::Decomposition;
 use crate::matrix::Matrix4x4;
 use crate::ray::Ray;
 use crate::world::Intersection;
 use crate::material::Material;
 use crate::math;
+use crate::math::Float;
+use crate::stats;
 
-enum ShapeType {
+#[derive(Clone, Copy)]
+pub enum ShapeType {
     Sphere,
     Plane
 }
 
+impl ShapeType {
+    pub const ALL: [ShapeType; 2] = [ShapeType::Sphere, ShapeType::Plane];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShapeType::Sphere => "sphere",
+            ShapeType::Plane => "plane"
+        }
+    }
+}
+
 pub struct Shape {
     shape_type: ShapeType,
     pub material: Material,
-    pub transform: Matrix4x4
+    pub transform: Matrix4x4,
+    // see set_end_transform
+    motion: Option<Motion>
+}
+
+// the two poses of a moving shape, decomposed once for interpolating between
+struct Motion {
+    start: Matrix4x4,
+    start_parts: Decomposition,
+    end: Matrix4x4,
+    end_parts: Decomposition
 }
 
 impl Shape {
@@ -25,7 +50,8 @@ impl Shape {
         Shape {
             shape_type: ShapeType::Sphere,
             material: Material::new(),
-            transform: matrix::MATRIX_4X4_IDENTITY
+            transform: matrix::MATRIX_4X4_IDENTITY,
+            motion: None
         }
     }
 
@@ -37,13 +63,56 @@ impl Shape {
         Shape {
             shape_type: ShapeType::Plane,
             material: Material::new(),
-            transform: matrix::MATRIX_4X4_IDENTITY
+            transform: matrix::MATRIX_4X4_IDENTITY,
+            motion: None
         }
     }
 
-    pub fn intersects<'a>(&'a self, ray: &Ray) -> Vec<Intersection<'a>> {
+    /*
+        for moving shapes, the transform at time 1 while transform
+        is the one at time 0, in between they are interpolated, None
+        keeps the shape still
+
+        set it after transform, a later change to transform still
+        works but is decomposed again for every ray
+    */
+    pub fn set_end_transform(&mut self, end_transform: Option<Matrix4x4>) {
+        self.motion = end_transform.map(|end| Motion {
+            start: self.transform.clone(),
+            start_parts: self.transform.decompose(),
+            end_parts: end.decompose(),
+            end
+        });
+    }
+
+    pub fn end_transform(&self) -> Option<&Matrix4x4> {
+        self.motion.as_ref().map(|motion| &motion.end)
+    }
+
+    // the transform at a time in 0 to 1, earlier or later times hold still
+    pub fn transform_at(&self, time: Float) -> Matrix4x4 {
+        match &self.motion {
+            None => self.transform.clone(),
+            Some(_) if time <= 0.0 => self.transform.clone(),
+            Some(motion) if time >= 1.0 => motion.end.clone(),
+            Some(motion) => {
+                let decomposed;
+                let start_parts = if motion.start == self.transform {
+                    &motion.start_parts
+                }
+                else {
+                    decomposed = self.transform.decompose();
+                    &decomposed
+                };
+                start_parts.interpolate(&motion.end_parts, time).to_matrix()
+            }
+        }
+    }
+
+    pub(crate) fn intersects<'a>(&'a self, ray: &Ray) -> Vec<Intersection<'a>> {
         // transform the ray using the sphere transform before anything
-        let local_ray = ray.transform(&self.transform.inverse());
+        let local_ray = ray.transform(&self.transform_at(ray.time).inverse());
+        stats::count_intersection_test(self.shape_type);
         match self.shape_type {
             ShapeType::Sphere => sphere_intersects(&self, &local_ray),
             ShapeType::Plane => plane_intersects(&self, &local_ray)
@@ -51,7 +120,11 @@ impl Shape {
     }
 
     pub fn normal_at(&self, world_point: &Point) -> Vector {
-        let inverted_transform = self.transform.inverse();
+        self.normal_at_time(world_point, 0.0)
+    }
+
+    pub fn normal_at_time(&self, world_point: &Point, time: Float) -> Vector {
+        let inverted_transform = self.transform_at(time).inverse();
         let transposed_inverted_transform = inverted_transform.transpose();
         let local_point = inverted_transform.multiply_tuple(&world_point);
     
@@ -149,7 +222,7 @@ fn ray_sphere_intersects_test() {
 #[test]
 fn sphere_normal_at_origin_test() {
     let sphere = Shape::sphere();
-    let sqrt3div3 = 3_f32.sqrt()/3.0;
+    let sqrt3div3 = (3.0 as math::Float).sqrt()/3.0;
 
     assert_eq!(sphere.normal_at(&point!(1,0,0)), vector!(1,0,0));
     assert_eq!(sphere.normal_at(&point!(0,1,0)), vector!(0,1,0));
@@ -169,8 +242,8 @@ fn sphere_normal_at_transformed_test() {
     assert_eq!(n, vector!(0, 0.70711, -0.70711));
 
     // scaled (squashed) and rotated test
-    sphere.transform = Matrix4x4::scaling(1.0, 0.5, 1.0).multiply(&Matrix4x4::rotation_z(std::f32::consts::PI));
-    let n = sphere.normal_at(&point!(0, 2_f32.sqrt()/2.0, -2_f32.sqrt()/2.0));
+    sphere.transform = Matrix4x4::scaling(1.0, 0.5, 1.0).multiply(&Matrix4x4::rotation_z(math::consts::PI));
+    let n = sphere.normal_at(&point!(0, (2.0 as math::Float).sqrt()/2.0, -(2.0 as math::Float).sqrt()/2.0));
     assert_eq!(n, vector!(0, 0.97014, -0.24254));
 }
 
@@ -212,4 +285,47 @@ fn plane_intersects_test() {
     let xs = plane_intersects(&plane, &ray);
     assert_eq!(1, xs.len());
     assert_eq!(1.0, xs[0].t);
-}
\ No newline at end of file
+}
+
+#[test]
+fn moving_shape_test() {
+    let mut sphere = Shape::sphere();
+    sphere.set_end_transform(Some(Matrix4x4::translation(4.0, 0.0, 0.0)
+        .multiply(&Matrix4x4::rotation_y(math::consts::PI / 2.0))));
+
+    // interpolated halfway in translation and rotation
+    assert_eq!(sphere.transform_at(0.0), matrix::MATRIX_4X4_IDENTITY);
+    assert_eq!(sphere.transform_at(0.5), Matrix4x4::translation(2.0, 0.0, 0.0)
+        .multiply(&Matrix4x4::rotation_y(math::consts::PI / 4.0)));
+    assert_eq!(sphere.transform_at(2.0), Matrix4x4::translation(4.0, 0.0, 0.0)
+        .multiply(&Matrix4x4::rotation_y(math::consts::PI / 2.0)));
+
+    // rays hit the sphere where it is at their time
+    let ray = Ray::at_time(point!(2,0,-5), vector!(0,0,1), 0.0);
+    assert_eq!(0, sphere.intersects(&ray).len());
+    let ray = Ray::at_time(point!(2,0,-5), vector!(0,0,1), 0.5);
+    let xs = sphere.intersects(&ray);
+    assert_eq!(2, xs.len());
+    assert!(math::fequal(4.0, xs[0].t));
+    assert_eq!(sphere.normal_at_time(&point!(2,0,-1), 0.5), vector!(0,0,-1));
+
+    // a still shape ignores time
+    let still = Shape::sphere();
+    assert_eq!(2, still.intersects(&Ray::at_time(point!(0,0,-5), vector!(0,0,1), 0.7)).len());
+
+    // the ends are the transforms as given, shear and all
+    let shear = Matrix4x4::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    let mut sheared = Shape::sphere();
+    sheared.transform = shear.clone();
+    sheared.set_end_transform(Some(shear.multiply(&Matrix4x4::translation(1.0, 0.0, 0.0))));
+    assert_eq!(sheared.transform_at(0.0), shear);
+    assert_eq!(sheared.transform_at(1.0), shear.multiply(&Matrix4x4::translation(1.0, 0.0, 0.0)));
+
+    // a transform changed after the end was set still moves from where it is
+    sphere.transform = Matrix4x4::translation(2.0, 0.0, 0.0);
+    assert_eq!(sphere.transform_at(0.5), Matrix4x4::translation(3.0, 0.0, 0.0)
+        .multiply(&Matrix4x4::rotation_y(math::consts::PI / 4.0)));
+    sphere.set_end_transform(None);
+    assert!(sphere.end_transform().is_none());
+    assert_eq!(sphere.transform_at(0.5), Matrix4x4::translation(2.0, 0.0, 0.0));
+}
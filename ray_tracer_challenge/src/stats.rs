@@ -0,0 +1,255 @@
+use crate::render::Region;
+use crate::shape::ShapeType;
+use std::cell::RefCell;
+use std::time::Duration;
+
+/*
+    counters collected while rendering, to see where the time goes
+
+    they are only kept inside stats::collect and only for the thread
+    that called it, renders do all their work on the calling thread
+    so two renders at once each get their own counts and nothing is
+    held on to once collect returns
+
+    there is no bounding volume hierarchy yet, every ray is tested
+    against every object, so intersection_tests is also what one
+    would save and bvh_node_visits stays 0, it is there so reports
+    and their json keep the same fields once there is one
+*/
+thread_local! {
+    static COLLECTING: RefCell<Option<Stats>> = const { RefCell::new(None) };
+}
+
+// runs count on the stats being collected, if any
+fn count(count: impl FnOnce(&mut Stats)) {
+    COLLECTING.with(|collecting| {
+        if let Some(stats) = collecting.borrow_mut().as_mut() {
+            count(stats);
+        }
+    });
+}
+
+pub(crate) fn count_primary_ray() {
+    count(|stats| stats.primary_rays += 1);
+}
+
+pub(crate) fn count_shadow_ray() {
+    count(|stats| stats.shadow_rays += 1);
+}
+
+pub(crate) fn count_intersection_test(shape_type: ShapeType) {
+    count(|stats| stats.intersection_tests[shape_type as usize].1 += 1);
+}
+
+pub(crate) fn count_matrix_inversion() {
+    count(|stats| stats.matrix_inversions += 1);
+}
+
+/*
+    a tile rendered again, by a later progressive pass or another
+    frame, adds its time to the entry it already has so there are
+    never more entries than the image has tiles
+*/
+pub(crate) fn record_tile(region: &Region, time: Duration) {
+    count(|stats| {
+        match stats.tiles.iter_mut().find(|tile| tile.region == *region) {
+            Some(tile) => tile.time += time,
+            None => stats.tiles.push(TileTime { region: region.clone(), time })
+        }
+    });
+}
+
+/*
+    runs render and returns what it did along with the stats of
+    everything it rendered, stats collected around it are set aside
+    until it is done
+*/
+pub fn collect<T>(render: impl FnOnce() -> T) -> (T, Stats) {
+    let outer = COLLECTING.with(|collecting| collecting.replace(Some(Stats::new())));
+    let result = render();
+    let stats = COLLECTING.with(|collecting| collecting.replace(outer));
+    (result, stats.expect("stats were taken while collecting"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileTime {
+    pub region: Region,
+    pub time: Duration
+}
+
+pub struct Stats {
+    pub primary_rays: u64,
+    pub shadow_rays: u64,
+    // ray against shape tests by shape type name
+    pub intersection_tests: Vec<(&'static str, u64)>,
+    pub matrix_inversions: u64,
+    pub bvh_node_visits: u64,
+    // in the order they were first rendered
+    pub tiles: Vec<TileTime>
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            primary_rays: 0,
+            shadow_rays: 0,
+            intersection_tests: ShapeType::ALL.iter().map(|shape_type| (shape_type.name(), 0)).collect(),
+            matrix_inversions: 0,
+            bvh_node_visits: 0,
+            tiles: Vec::new()
+        }
+    }
+
+    pub fn render_time(&self) -> Duration {
+        self.tiles.iter().map(|tile| tile.time).sum()
+    }
+
+    pub fn slowest_tile(&self) -> Option<&TileTime> {
+        self.tiles.iter().max_by_key(|tile| tile.time)
+    }
+
+    // a few lines for people
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        report.push_str(&format!("primary rays       {}\n", self.primary_rays));
+        report.push_str(&format!("shadow rays        {}\n", self.shadow_rays));
+        for (name, count) in self.intersection_tests.iter() {
+            report.push_str(&format!("{:18} {}\n", format!("{} tests", name), count));
+        }
+        report.push_str(&format!("matrix inversions  {}\n", self.matrix_inversions));
+        report.push_str(&format!("bvh node visits    {}\n", self.bvh_node_visits));
+        report.push_str(&format!("tiles              {} in {:.3}s\n", self.tiles.len(), self.render_time().as_secs_f64()));
+        if let Some(tile) = self.slowest_tile() {
+            let region = &tile.region;
+            report.push_str(&format!(
+                "slowest tile       {}x{} at {},{} in {:.3}s\n",
+                region.width, region.height, region.x, region.y, tile.time.as_secs_f64()));
+        }
+        report
+    }
+
+    // the same for other programs, times in seconds
+    pub fn to_json(&self) -> String {
+        let intersection_tests: Vec<String> = self.intersection_tests.iter()
+            .map(|(name, count)| format!("\"{}\": {}", name, count))
+            .collect();
+        let tiles: Vec<String> = self.tiles.iter()
+            .map(|tile| format!(
+                "{{\"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}, \"seconds\": {}}}",
+                tile.region.x, tile.region.y, tile.region.width, tile.region.height, tile.time.as_secs_f64()))
+            .collect();
+        format!(
+            "{{\"primary_rays\": {}, \"shadow_rays\": {}, \"intersection_tests\": {{{}}}, \"matrix_inversions\": {}, \"bvh_node_visits\": {}, \"render_seconds\": {}, \"tiles\": [{}]}}",
+            self.primary_rays,
+            self.shadow_rays,
+            intersection_tests.join(", "),
+            self.matrix_inversions,
+            self.bvh_node_visits,
+            self.render_time().as_secs_f64(),
+            tiles.join(", "))
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats::new()
+    }
+}
+
+#[test]
+fn report_test() {
+    let stats = Stats {
+        primary_rays: 100,
+        shadow_rays: 40,
+        intersection_tests: vec![("sphere", 7), ("plane", 3)],
+        matrix_inversions: 10,
+        bvh_node_visits: 0,
+        tiles: vec![
+            TileTime { region: Region::new(0, 0, 16, 16), time: Duration::from_millis(250) },
+            TileTime { region: Region::new(16, 0, 4, 16), time: Duration::from_millis(500) }
+        ]
+    };
+    assert_eq!(stats.render_time(), Duration::from_millis(750));
+    assert_eq!(stats.slowest_tile().unwrap().region, Region::new(16, 0, 4, 16));
+    assert!(stats.report().contains("sphere tests       7\n"));
+    assert!(stats.report().contains("bvh node visits    0\n"));
+    assert!(stats.report().contains("slowest tile       4x16 at 16,0 in 0.500s\n"));
+    assert_eq!(
+        stats.to_json(),
+        "{\"primary_rays\": 100, \"shadow_rays\": 40, \"intersection_tests\": {\"sphere\": 7, \"plane\": 3}, \
+        \"matrix_inversions\": 10, \"bvh_node_visits\": 0, \"render_seconds\": 0.75, \"tiles\": [\
+        {\"x\": 0, \"y\": 0, \"width\": 16, \"height\": 16, \"seconds\": 0.25}, \
+        {\"x\": 16, \"y\": 0, \"width\": 4, \"height\": 16, \"seconds\": 0.5}]}");
+}
+
+#[test]
+fn counters_test() {
+    use crate::canvas::Canvas;
+    use crate::render;
+    use crate::render::RenderSettings;
+    use crate::shape::Shape;
+
+    let (camera, mut world) = render::test_scene(20, 20);
+    let mut floor = Shape::plane();
+    floor.transform = crate::matrix::Matrix4x4::translation(0.0, -1.0, 0.0);
+    world.objects.push(floor);
+
+    let mut canvas = Canvas::new(20, 20);
+    let ((), stats) = collect(|| render::render(&camera, &world, &RenderSettings::new(), &mut canvas));
+    assert_eq!(stats.primary_rays, 400);
+    assert!(stats.shadow_rays > 0);
+    // every ray is tested against both shapes
+    assert_eq!(stats.intersection_tests, vec![("sphere", 400 + stats.shadow_rays), ("plane", 400 + stats.shadow_rays)]);
+    assert!(stats.matrix_inversions >= 800);
+    // a 16 pixel tile, two cut short by the edge and a corner
+    let tiles: Vec<&Region> = stats.tiles.iter().map(|tile| &tile.region).collect();
+    assert_eq!(tiles, vec![
+        &Region::new(0, 0, 16, 16),
+        &Region::new(16, 0, 4, 16),
+        &Region::new(0, 16, 16, 4),
+        &Region::new(16, 16, 4, 4)]);
+
+    // nothing is kept outside collect, and collecting again starts over
+    render::render(&camera, &world, &RenderSettings::new(), &mut canvas);
+    let ((), again) = collect(|| ());
+    assert_eq!(again.primary_rays, 0);
+    assert!(again.tiles.is_empty());
+
+    // a render inside another collect is counted by the inner one
+    let (inner, outer) = collect(|| {
+        render::render_pixel(&camera, &world, &RenderSettings::new(), 0, 0);
+        collect(|| render::render(&camera, &world, &RenderSettings::new(), &mut canvas)).1
+    });
+    assert_eq!(outer.primary_rays, 1);
+    assert_eq!(inner.primary_rays, 400);
+}
+
+#[test]
+fn every_render_records_tiles_test() {
+    use crate::aov;
+    use crate::canvas::Canvas;
+    use crate::progressive::{CancelToken, ProgressiveRender};
+    use crate::render;
+    use crate::render::RenderSettings;
+
+    let (camera, world) = render::test_scene(7, 5);
+    let settings = RenderSettings::new();
+    let frame = vec![Region::new(0, 0, 7, 5)];
+    let regions = |stats: &Stats| stats.tiles.iter().map(|tile| tile.region.clone()).collect::<Vec<Region>>();
+
+    let crop = Region::new(3, 2, 4, 3);
+    let (_, stats) = collect(|| render::render_crop(&camera, &world, &settings, &crop));
+    assert_eq!(regions(&stats), vec![crop]);
+
+    let (_, stats) = collect(|| render::render_with_heatmap(&camera, &world, &settings, &mut Canvas::new(7, 5)));
+    assert_eq!(regions(&stats), frame);
+    let (_, stats) = collect(|| aov::render_with_aovs(&camera, &world, &settings, &mut Canvas::new(7, 5)));
+    assert_eq!(regions(&stats), frame);
+
+    // passes over the same tile add up in one entry
+    let (_, stats) = collect(|| {
+        ProgressiveRender::new(7, 5, &settings).run(&camera, &world, &settings, 3, None, &CancelToken::new())
+    });
+    assert_eq!(regions(&stats), frame);
+    assert_eq!(stats.primary_rays, 3 * 35);
+}
@@ -0,0 +1,241 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::math::Float;
+use crate::math::consts;
+use crate::tuple::Point;
+
+/*
+    ways of flattening a 3d point onto u, v texture coordinates
+    in 0 to 1, v = 0 is the bottom of the image
+*/
+pub enum UvMapping {
+    // longitude and latitude around the origin, for spheres
+    Spherical,
+    // x and z repeating every unit, for planes
+    Planar,
+    // around the y axis with y repeating every unit, for cylinders
+    Cylindrical,
+    // the six faces of a cube laid out as a horizontal cross
+    Cube
+}
+
+pub enum TextureFilter {
+    Nearest,
+    Bilinear
+}
+
+impl UvMapping {
+    pub fn map(&self, point: &Point) -> (Float, Float) {
+        match self {
+            UvMapping::Spherical => spherical_map(point),
+            UvMapping::Planar => planar_map(point),
+            UvMapping::Cylindrical => cylindrical_map(point),
+            UvMapping::Cube => cube_map(point)
+        }
+    }
+}
+
+fn spherical_map(point: &Point) -> (Float, Float) {
+    // azimuthal angle, -pi to pi increasing clockwise looking down y
+    let theta = point.x.atan2(point.z);
+    let radius = (point.x * point.x + point.y * point.y + point.z * point.z).sqrt();
+    // polar angle, 0 to pi
+    let phi = (point.y / radius).acos();
+    let raw_u = theta / (2.0 * consts::PI);
+    // flip u so it increases counter clockwise from the outside
+    let u = 1.0 - (raw_u + 0.5);
+    let v = 1.0 - phi / consts::PI;
+    (u, v)
+}
+
+fn planar_map(point: &Point) -> (Float, Float) {
+    (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0))
+}
+
+fn cylindrical_map(point: &Point) -> (Float, Float) {
+    let theta = point.x.atan2(point.z);
+    let raw_u = theta / (2.0 * consts::PI);
+    let u = 1.0 - (raw_u + 0.5);
+    (u, point.y.rem_euclid(1.0))
+}
+
+/*
+    the cube faces are unfolded into a 4 x 3 cross
+
+              up
+        left front right back
+              down
+
+    any point is projected onto the cube along its largest axis
+    so directions work as well as points on a unit cube
+*/
+fn cube_map(point: &Point) -> (Float, Float) {
+    let ax = point.x.abs();
+    let ay = point.y.abs();
+    let az = point.z.abs();
+    let max = ax.max(ay).max(az);
+    let x = point.x / max;
+    let y = point.y / max;
+    let z = point.z / max;
+
+    // face u, v in 0 to 1 and the column, row of the face in the cross
+    let (u, v, col, row) = if max == ax && x > 0.0 {
+        ((1.0 - z) / 2.0, (y + 1.0) / 2.0, 2.0, 1.0)
+    }
+    else if max == ax {
+        ((z + 1.0) / 2.0, (y + 1.0) / 2.0, 0.0, 1.0)
+    }
+    else if max == ay && y > 0.0 {
+        ((x + 1.0) / 2.0, (1.0 - z) / 2.0, 1.0, 0.0)
+    }
+    else if max == ay {
+        ((x + 1.0) / 2.0, (z + 1.0) / 2.0, 1.0, 2.0)
+    }
+    else if z > 0.0 {
+        ((x + 1.0) / 2.0, (y + 1.0) / 2.0, 1.0, 1.0)
+    }
+    else {
+        ((1.0 - x) / 2.0, (y + 1.0) / 2.0, 3.0, 1.0)
+    };
+
+    // rounding can push a sample just past the edge of its face
+    let u = u.clamp(0.0, 1.0);
+    let v = v.clamp(0.0, 1.0);
+    ((col + u) / 4.0, (2.0 - row + v) / 3.0)
+}
+
+/*
+    an image wrapped onto a shape through a uv mapping
+*/
+pub struct Texture {
+    pub canvas: Canvas,
+    pub mapping: UvMapping,
+    pub filter: TextureFilter
+}
+
+impl Texture {
+    pub fn new(canvas: Canvas, mapping: UvMapping, filter: TextureFilter) -> Texture {
+        Texture {
+            canvas,
+            mapping,
+            filter
+        }
+    }
+
+    pub fn color_at(&self, point: &Point) -> Color {
+        let (u, v) = self.mapping.map(point);
+        self.sample(u, v)
+    }
+
+    pub fn sample(&self, u: Float, v: Float) -> Color {
+        match self.filter {
+            TextureFilter::Nearest => self.canvas.sample_nearest(u, v),
+            TextureFilter::Bilinear => self.canvas.sample_bilinear(u, v)
+        }
+    }
+}
+
+#[test]
+fn spherical_map_test() {
+    use crate::point;
+
+    let frac = consts::FRAC_1_SQRT_2;
+    let cases = [
+        (point!(0,0,-1), 0.0, 0.5),
+        (point!(1,0,0), 0.25, 0.5),
+        (point!(0,0,1), 0.5, 0.5),
+        (point!(-1,0,0), 0.75, 0.5),
+        (point!(0,1,0), 0.5, 1.0),
+        (point!(0,-1,0), 0.5, 0.0),
+        (point!(frac,frac,0), 0.25, 0.75)];
+    for (p, u, v) in cases.iter() {
+        let (tu, tv) = UvMapping::Spherical.map(p);
+        assert!(crate::math::fequal(*u, tu) && crate::math::fequal(*v, tv));
+    }
+}
+
+#[test]
+fn planar_map_test() {
+    use crate::point;
+
+    let cases = [
+        (point!(0.25,0,0.5), 0.25, 0.5),
+        (point!(0.25,0,-0.25), 0.25, 0.75),
+        (point!(0.25,0.5,-0.25), 0.25, 0.75),
+        (point!(1.25,0,0.5), 0.25, 0.5),
+        (point!(0.25,0,-1.75), 0.25, 0.25),
+        (point!(1,0,-1), 0.0, 0.0),
+        (point!(0,0,0), 0.0, 0.0)];
+    for (p, u, v) in cases.iter() {
+        let (tu, tv) = UvMapping::Planar.map(p);
+        assert!(crate::math::fequal(*u, tu) && crate::math::fequal(*v, tv));
+    }
+}
+
+#[test]
+fn cylindrical_map_test() {
+    use crate::point;
+
+    let frac = consts::FRAC_1_SQRT_2;
+    let cases = [
+        (point!(0,0,-1), 0.0, 0.0),
+        (point!(0,0.5,-1), 0.0, 0.5),
+        (point!(0,1,-1), 0.0, 0.0),
+        (point!(frac,0.5,-frac), 0.125, 0.5),
+        (point!(1,0.5,0), 0.25, 0.5),
+        (point!(frac,0.5,frac), 0.375, 0.5),
+        (point!(0,-0.25,1), 0.5, 0.75),
+        (point!(-frac,0.5,frac), 0.625, 0.5),
+        (point!(-1,1.25,0), 0.75, 0.25),
+        (point!(-frac,0.5,-frac), 0.875, 0.5)];
+    for (p, u, v) in cases.iter() {
+        let (tu, tv) = UvMapping::Cylindrical.map(p);
+        assert!(crate::math::fequal(*u, tu) && crate::math::fequal(*v, tv));
+    }
+}
+
+#[test]
+fn cube_map_test() {
+    use crate::point;
+
+    // the center of each face lands in the center of its cell in the cross
+    let cases = [
+        (point!(-1,0,0), 0.125, 0.5),
+        (point!(0,0,1), 0.375, 0.5),
+        (point!(1,0,0), 0.625, 0.5),
+        (point!(0,0,-1), 0.875, 0.5),
+        (point!(0,1,0), 0.375, 5.0 / 6.0),
+        (point!(0,-1,0), 0.375, 1.0 / 6.0),
+        // directions are projected onto the cube first
+        (point!(0,0,5), 0.375, 0.5),
+        // corners of the front face
+        (point!(-1,-1,1), 0.25, 1.0 / 3.0),
+        (point!(0.9,0.9,1), 0.4875, 0.65)];
+    for (p, u, v) in cases.iter() {
+        let (tu, tv) = UvMapping::Cube.map(p);
+        assert!(crate::math::fequal(*u, tu) && crate::math::fequal(*v, tv));
+    }
+}
+
+#[test]
+fn texture_color_at_test() {
+    use crate::point;
+
+    let mut canvas = Canvas::new(2, 2);
+    canvas.set_pixel(0, 0, &crate::rgb!(1,0,0));
+    canvas.set_pixel(1, 0, &crate::rgb!(0,1,0));
+    canvas.set_pixel(0, 1, &crate::rgb!(0,0,1));
+    canvas.set_pixel(1, 1, &Color::WHITE);
+
+    // planar u is x and v is z, v = 1 is the top row of the image
+    let texture = Texture::new(canvas, UvMapping::Planar, TextureFilter::Nearest);
+    assert_eq!(crate::rgb!(0,0,1), texture.color_at(&point!(0.1,0,0.1)));
+    assert_eq!(crate::rgb!(1,0,0), texture.color_at(&point!(0.1,0,0.9)));
+    assert_eq!(Color::WHITE, texture.color_at(&point!(0.9,0,0.1)));
+    assert_eq!(crate::rgb!(0,1,0), texture.color_at(&point!(1.9,0,-0.1)));
+
+    // bilinear blends the four texels
+    let texture = Texture::new(texture.canvas, UvMapping::Planar, TextureFilter::Bilinear);
+    assert_eq!(crate::rgb!(0.5,0.5,0.5), texture.color_at(&point!(0.5,0,0.5)));
+    assert_eq!(crate::rgb!(0.5,0,0.5), texture.color_at(&point!(0,0,0.5)));
+}
@@ -0,0 +1,115 @@
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::math::Float;
+
+/*
+    ways of squeezing unbounded scene colors into 0 to 1
+    for 8 bit output such as canvas::to_ppm and canvas::to_png
+*/
+pub enum ToneMap {
+    // cut off at 1, what the 8 bit writers do on their own
+    Clamp,
+    // x / (1 + x), never quite reaches 1 so highlights keep some detail
+    Reinhard,
+    // the fitted ACES filmic curve, a toe in the shadows and a soft shoulder
+    Aces
+}
+
+pub struct ToneMapping {
+    pub operator: ToneMap,
+    // in stops, each one doubles the brightness before the curve
+    pub exposure: Float,
+    // encode with the sRGB transfer curve displays expect
+    pub srgb: bool
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMap) -> ToneMapping {
+        ToneMapping {
+            operator,
+            exposure: 0.0,
+            srgb: true
+        }
+    }
+
+    pub fn map(&self, color: &Color) -> Color {
+        let scale = (2.0 as Float).powf(self.exposure);
+        let channel = |value: Float| {
+            let value = (value * scale).max(0.0);
+            let mapped = match self.operator {
+                ToneMap::Clamp => value.min(1.0),
+                ToneMap::Reinhard => value / (1.0 + value),
+                ToneMap::Aces => aces(value)
+            };
+            if self.srgb { linear_to_srgb(mapped) } else { mapped }
+        };
+        Color::rgb(channel(color.red), channel(color.green), channel(color.blue))
+    }
+
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        canvas.map(|color| self.map(color))
+    }
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference rendering transform
+fn aces(x: Float) -> Float {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    ((x * (a * x + b)) / (x * (c * x + d) + e)).clamp(0.0, 1.0)
+}
+
+pub fn linear_to_srgb(value: Float) -> Float {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.003_130_8 {
+        value * 12.92
+    }
+    // exactly, rounding would otherwise land just short and lose the top 8 bit level
+    else if value >= 1.0 {
+        1.0
+    }
+    else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[test]
+fn tone_map_test() {
+    let mut mapping = ToneMapping::new(ToneMap::Reinhard);
+    mapping.srgb = false;
+    assert_eq!(mapping.map(&Color::rgb(1.0, 3.0, 0.0)), Color::rgb(0.5, 0.75, 0.0));
+    // one stop up doubles the input
+    mapping.exposure = 1.0;
+    assert_eq!(mapping.map(&Color::rgb(0.5, 1.5, -1.0)), Color::rgb(0.5, 0.75, 0.0));
+
+    let mut mapping = ToneMapping::new(ToneMap::Clamp);
+    mapping.srgb = false;
+    assert_eq!(mapping.map(&Color::rgb(0.25, 7.0, -2.0)), Color::rgb(0.25, 1.0, 0.0));
+
+    // aces crushes black, keeps mid grey near the middle and rolls off to 1
+    let mut mapping = ToneMapping::new(ToneMap::Aces);
+    mapping.srgb = false;
+    assert_eq!(mapping.map(&Color::BLACK), Color::BLACK);
+    let grey = mapping.map(&Color::rgb(0.18, 0.18, 0.18)).red;
+    assert!(grey > 0.2 && grey < 0.3);
+    assert!(mapping.map(&Color::rgb(100.0, 0.0, 0.0)).red > 0.99);
+    assert!(mapping.map(&Color::rgb(2.0, 0.0, 0.0)).red < mapping.map(&Color::rgb(4.0, 0.0, 0.0)).red);
+}
+
+#[test]
+fn srgb_test() {
+    assert_eq!(linear_to_srgb(0.0), 0.0);
+    assert_eq!(linear_to_srgb(1.0), 1.0);
+    assert!(crate::math::fequal(linear_to_srgb(0.002), 0.02584));
+    // mid grey in linear light is about 46% in sRGB
+    assert!((linear_to_srgb(0.18) - 0.4614).abs() < 0.001);
+
+    let mut canvas = Canvas::new(1, 1);
+    canvas.set_pixel(0, 0, &Color::rgb(0.18, 4.0, 0.0));
+    let mapped = ToneMapping::new(ToneMap::Clamp).apply(&canvas);
+    let pixel = mapped.get_pixel(0, 0);
+    assert!((pixel.red - 0.4614).abs() < 0.001);
+    assert_eq!(pixel.green, 1.0);
+}
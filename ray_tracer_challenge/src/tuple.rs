@@ -1,14 +1,15 @@
 use crate::math::fequal;
+use crate::math::Float;
 use crate::tuple;
 use crate::vector;
 use crate::point;
 
 #[derive(Debug, Clone)]
 pub struct Tuple {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
-    pub w: f32 // 0 is a vector 1 is a point
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+    pub w: Float // 0 is a vector 1 is a point
 }
 
 pub type Point = Tuple;
@@ -38,7 +39,7 @@ impl Tuple {
         ZERO_VECTOR.subtract(&self)
     }
     
-    pub fn multiplyf(&self, rhs: f32) -> Tuple {
+    pub fn multiplyf(&self, rhs: Float) -> Tuple {
         Tuple {
             x: self.x * rhs,
             y: self.y * rhs,
@@ -47,7 +48,7 @@ impl Tuple {
         }
     }
 
-    pub fn dividef(&self, rhs: f32) -> Tuple {
+    pub fn dividef(&self, rhs: Float) -> Tuple {
         Tuple {
             x: self.x / rhs,
             y: self.y / rhs,
@@ -56,7 +57,7 @@ impl Tuple {
         }
     }
 
-    pub fn magnitude(&self) -> f32 {
+    pub fn magnitude(&self) -> Float {
         (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
     }
     
@@ -70,7 +71,7 @@ impl Tuple {
         )
     }
     
-    pub fn dot(&self, b: &Tuple) -> f32 {
+    pub fn dot(&self, b: &Tuple) -> Float {
         self.x * b.x + self.y * b.y + self.z * b.z + self.w * b.w
     }
     
@@ -191,7 +192,7 @@ fn divide_test() {
 fn magnitude_test() {
     let v = vector!(-1,-2,-3);
     let t = v.magnitude();
-    let r = (14.0 as f32).sqrt();
+    let r = (14.0 as Float).sqrt();
     assert_eq!(r, t);
 }
 
@@ -242,7 +243,7 @@ fn reflect_test() {
     // incoming vector coming in straight down
     let v = vector!(0,-1,0);
     // 45 degree normal
-    let n = vector!(2_f32.sqrt()/2.0, 2_f32.sqrt()/2.0, 0);
+    let n = vector!((2.0 as Float).sqrt()/2.0, (2.0 as Float).sqrt()/2.0, 0);
     let r = v.reflect(&n);
     // reflected vector should be horizontal
     assert_eq!(r, vector!(1,0,0));
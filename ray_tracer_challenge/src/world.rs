@@ -1,5 +1,9 @@
 use crate::light::Light;
-use crate::light::lighting;
+use crate::light::lighting_occluded;
+use crate::occlusion::AmbientOcclusion;
+use crate::media::Fog;
+use crate::sampling::Rng;
+use crate::stats;
 use crate::shape::Shape;
 use crate::matrix::Matrix4x4;
 use crate::ray::Ray;
@@ -10,46 +14,124 @@ use crate::point;
 use crate::vector;
 use crate::rgb;
 use crate::math;
+use crate::background::Background;
+use crate::math::Float;
 
 pub struct World {
     pub objects: Vec<Shape>,
-    pub lights: Vec<Light>
+    pub lights: Vec<Light>,
+    // seen by any ray that misses every object
+    pub background: Background,
+    // darkens material.ambient where nearby objects block the sky
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    // fades everything towards the fog color with distance
+    pub fog: Option<Fog>
 }
 
 impl World {
     pub fn new() -> World {
         World {
             objects: Vec::new(),
-            lights: Vec::new()
+            lights: Vec::new(),
+            background: Background::Solid(Color::BLACK),
+            ambient_occlusion: None,
+            fog: None
         }
     }
 
     pub fn color_at(&self, ray: &Ray) -> Color {
         let intersections = intersect(&self, &ray);
-        match hit(&intersections) {
-            None => Color::BLACK,
+        let hit = surface_hit(&intersections);
+        let mut color = match hit {
+            None => self.background.color_at(&ray.direction),
             Some(intersection) => {
-                let comps = prepare_computations(&ray, &intersection);
-                let color = shade_hit(&self, &comps);
-                return color;
+                let comps = prepare_computations(ray, intersection);
+                shade_hit(self, &comps)
             }
+        };
+
+        // between one volume boundary and the next the volumes the ray is
+        // in fill it and then the fog, working back from the surface to the eye
+        let end = hit.map_or(Float::INFINITY, |intersection| intersection.t);
+        let mut stops: Vec<Float> = intersections.iter()
+            .filter(|x| x.t >= 0.0 && x.t < end && x.object.material.volume.is_some())
+            .map(|x| x.t)
+            .collect();
+        stops.insert(0, 0.0);
+        stops.push(end);
+        for segment in stops.windows(2).rev() {
+            let (from, to) = (segment[0], segment[1]);
+            let segment_ray = Ray::at_time(ray.position(from), ray.direction.clone(), ray.time);
+            for object in self.objects.iter() {
+                if let Some(volume) = &object.material.volume {
+                    // a ray escaping to the background has left every closed volume
+                    if to.is_finite() && inside_at(&intersections, object, (from + to) / 2.0) {
+                        color = volume.integrate(self, &segment_ray, to - from, &color);
+                    }
+                }
+            }
+            if let Some(fog) = &self.fog {
+                color = fog.apply(&color, (to - from) * ray.direction.magnitude());
+            }
+        }
+        color
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
+    }
+}
+
+// whether the origin of the ray the intersections came from is inside a closed shape
+fn starts_inside(intersections: &[Intersection], object: &Shape) -> bool {
+    inside_at(intersections, object, 0.0)
+}
+
+// whether the point at t along the ray is inside a closed shape
+fn inside_at(intersections: &[Intersection], object: &Shape, t: Float) -> bool {
+    let ahead = intersections.iter()
+        .filter(|x| x.t >= t && std::ptr::eq(x.object, object))
+        .count();
+    ahead % 2 == 1
+}
+
+// how much of the ray from t = 0 to t = end lies inside a closed shape
+fn length_inside(intersections: &[Intersection], object: &Shape, end: Float) -> Float {
+    let mut inside = starts_inside(intersections, object);
+    let mut length = 0.0;
+    let mut from = 0.0;
+    for x in intersections.iter().filter(|x| x.t >= 0.0 && x.t < end && std::ptr::eq(x.object, object)) {
+        if inside {
+            length += x.t - from;
         }
+        inside = !inside;
+        from = x.t;
+    }
+    if inside {
+        length += end - from;
     }
+    length
 }
 
-struct HitComputations <'a> {
-    object: &'a Shape,
-    t: f32,
-    point: Point,
-    over_point: Point,
-    eyev: Vector,
-    normalv: Vector,
-    inside: bool
+pub struct HitComputations <'a> {
+    pub object: &'a Shape,
+    pub t: Float,
+    pub point: Point,
+    pub over_point: Point,
+    pub eyev: Vector,
+    pub normalv: Vector,
+    // normalv was flipped to face the eye, nothing outside the tests reads it yet
+    #[allow(dead_code)]
+    pub inside: bool,
+    // the time of the ray, for the rays traced from the hit
+    pub time: Float
 }
 
 pub struct Intersection<'a> {
     pub object: &'a Shape,
-    pub t: f32
+    pub t: Float
 }
 
 fn default_world() -> World {
@@ -72,9 +154,9 @@ fn default_world() -> World {
     return world;
 }
 
-fn prepare_computations<'a>(ray: &Ray, intersection: &Intersection<'a>) -> HitComputations<'a> {
+pub fn prepare_computations<'a>(ray: &Ray, intersection: &Intersection<'a>) -> HitComputations<'a> {
     let point = ray.position(intersection.t);
-    let mut normalv = intersection.object.normal_at(&point);
+    let mut normalv = intersection.object.normal_at_time(&point, ray.time);
     let eyev = ray.direction.negate();
     let inside: bool;
     if normalv.dot(&eyev) < 0.0 {
@@ -84,18 +166,24 @@ fn prepare_computations<'a>(ray: &Ray, intersection: &Intersection<'a>) -> HitCo
     else {
         inside = false;
     }
+    // offset along the geometric normal, bumps would push the point into the surface
+    let over_point = point.add(&normalv.multiplyf(math::EPSILON));
+    if let Some(perturbation) = &intersection.object.material.normal_perturbation {
+        normalv = perturbation.perturb(intersection.object, &point, &normalv, ray.time);
+    }
     HitComputations {
         object: &intersection.object,
         t: intersection.t,
         point: point.clone(),
-        over_point: point.add(&normalv.multiplyf(math::EPSILON)),
+        over_point,
         eyev,
         normalv,
-        inside
+        inside,
+        time: ray.time
     }
 }
 
-fn intersect<'a>(world: &'a World, ray: &'a Ray) -> Vec<Intersection<'a>> {
+pub fn intersect<'a>(world: &'a World, ray: &'a Ray) -> Vec<Intersection<'a>> {
     let mut intersections = Vec::<Intersection>::with_capacity(world.objects.len() * 2);
     for obj in world.objects.iter() {
         let mut obj_intersections = obj.intersects(&ray);
@@ -105,6 +193,8 @@ fn intersect<'a>(world: &'a World, ray: &'a Ray) -> Vec<Intersection<'a>> {
     return intersections;
 }
 
+// the first hit of any kind, renders want surface_hit
+#[cfg(test)]
 pub fn hit<'a>(xs: &'a Vec<Intersection>) -> Option<&'a Intersection<'a>> {
     if xs.len() == 0 {
         None
@@ -112,14 +202,14 @@ pub fn hit<'a>(xs: &'a Vec<Intersection>) -> Option<&'a Intersection<'a>> {
     else {
         // try to find the lowest positive value
         let mut result = xs.first();
-        let mut r = f32::MAX;
+        let mut r = Float::MAX;
         for x in xs {
             if x.t >= 0.0 && x.t < r {
                 r = x.t;
                 result = Some(&x);
             }
         }
-        if r == f32::MAX {
+        if r == Float::MAX {
             None
         }
         else {
@@ -128,42 +218,80 @@ pub fn hit<'a>(xs: &'a Vec<Intersection>) -> Option<&'a Intersection<'a>> {
     }
 }
 
+/*
+    hit for everything that shades a surface, the boundaries of volumes
+    are not drawn so the ray carries on through them to the first
+    surface, xs sorted as intersect returns them
+*/
+pub fn surface_hit<'a>(xs: &'a [Intersection<'a>]) -> Option<&'a Intersection<'a>> {
+    xs.iter().find(|x| x.t >= 0.0 && x.object.material.volume.is_none())
+}
 
 fn shade_hit(world: &World, comps: &HitComputations) -> Color {
     let mut result: Color = Color::BLACK;
+    // seeded by the point so a still image does not change between renders
+    let visibility = match &world.ambient_occlusion {
+        Some(ao) => ao.visibility(world, &comps.over_point, &comps.normalv, comps.time, &mut Rng::for_point(&comps.point)),
+        None => 1.0
+    };
     for light in world.lights.iter() {
-        let in_shadow = is_shadowed(&world, &comps.over_point, &light);
-        let color = lighting(
+        let transmittance = shadow_transmittance(world, &comps.over_point, light, comps.time);
+        let color = lighting_occluded(
             &comps.object.material,
             &comps.object,
             &light,
             &comps.point,
             &comps.eyev,
             &comps.normalv,
-            in_shadow);
+            &transmittance,
+            visibility,
+            comps.time);
         result = result.add(&color);
     }
     return result;
 }
 
-fn is_shadowed(world: &World, point: &Point, light: &Light) -> bool {
-    let v = light.position.subtract(&point);
+/*
+    the fraction of the light's intensity that reaches point, white
+    when nothing is in the way and black behind an opaque object
+
+    every surface crossed between point and the light filters the
+    light by its transparency and color, so a closed shape like a
+    glass sphere filters it twice, once going in and once coming out,
+    volumes and fog take away what they absorb along the way
+*/
+pub fn shadow_transmittance(world: &World, point: &Point, light: &Light, time: Float) -> Color {
+    let v = light.position.subtract(point);
     let distance = v.magnitude();
     let direction = v.normalize();
-    let ray = Ray::new(point.clone(), direction);
-    let intersections = intersect(&world, &ray);
-    match hit(&intersections) {
-        None => false,
-        Some(h) => {
-            if h.t < distance {
-                true
-            }
-            else {
-                false
-            }
+    let ray = Ray::at_time(point.clone(), direction, time);
+    stats::count_shadow_ray();
+    let intersections = intersect(world, &ray);
+    let mut transmittance = Color::WHITE;
+    for intersection in intersections.iter() {
+        let material = &intersection.object.material;
+        if intersection.t < 0.0 || !material.casts_shadow || material.volume.is_some() {
+            continue;
+        }
+        if intersection.t >= distance {
+            break;
         }
+        if material.transparency <= 0.0 {
+            return Color::BLACK;
+        }
+        let filter = material.color_at_time(intersection.object, &ray.position(intersection.t), ray.time);
+        transmittance = transmittance.hadamard(&filter.multiplyf(material.transparency));
     }
-    
+    for object in world.objects.iter() {
+        if let (Some(volume), true) = (&object.material.volume, object.material.casts_shadow) {
+            let length = length_inside(&intersections, object, distance);
+            transmittance = transmittance.multiplyf(volume.transmittance(length));
+        }
+    }
+    if let Some(fog) = &world.fog {
+        transmittance = transmittance.multiplyf(fog.transmittance(distance));
+    }
+    transmittance
 }
 
 #[test]
@@ -239,6 +367,20 @@ fn prepare_computations_intersection_inside_sphere_test() {
 }
 
 
+#[test]
+fn prepare_computations_perturbed_normal_test() {
+    use crate::material::NormalPerturbation;
+
+    let ray = Ray::new(point!(0,0,-5), vector!(0,0,1));
+    let mut sphere = Shape::sphere();
+    sphere.material.normal_perturbation = Some(NormalPerturbation::Noise { scale: 8.0, amount: 2.0, octaves: 2 });
+    let intersection = Intersection { object: &sphere, t: 4.0 };
+    let comps = prepare_computations(&ray, &intersection);
+    // shading uses the bumped normal but the shadow offset stays on the geometric one
+    assert!(comps.normalv != vector!(0,0,-1));
+    assert_eq!(comps.over_point, point!(0,0,-1.0 - math::EPSILON));
+}
+
 #[test]
 fn shade_hit_test() {
     let mut world = default_world();
@@ -274,6 +416,12 @@ fn color_at_test() {
     let color = world.color_at(&ray);
     assert_eq!(color, rgb!(0.38066, 0.47583, 0.2855));
 
+    // a miss sees the background
+    world.background = Background::Gradient { bottom: Color::BLACK, top: rgb!(0,0,1) };
+    let ray = Ray::new(point!(0,0,-5), vector!(0,1,0));
+    let color = world.color_at(&ray);
+    assert_eq!(color, rgb!(0,0,1));
+
     let outer = world.objects.first_mut().unwrap();
     outer.material.ambient = 1.0;
     let inner = world.objects.last_mut().unwrap();
@@ -284,23 +432,168 @@ fn color_at_test() {
 }
 
 #[test]
-fn is_shadowed_test() {
+fn shadow_transmittance_test() {
     let w = default_world();
     let light = w.lights.first().unwrap();
 
     let p = point!(0,10,0);
-    let in_shadow = is_shadowed(&w, &p, &light);
-    assert_eq!(false, in_shadow);
+    assert_eq!(Color::WHITE, shadow_transmittance(&w, &p, &light, 0.0));
 
     let p = point!(10,-10,10);
-    let in_shadow = is_shadowed(&w, &p, &light);
-    assert_eq!(true, in_shadow);
+    assert_eq!(Color::BLACK, shadow_transmittance(&w, &p, &light, 0.0));
 
     let p = point!(-20,20,-20);
-    let in_shadow = is_shadowed(&w, &p, &light);
-    assert_eq!(false, in_shadow);
+    assert_eq!(Color::WHITE, shadow_transmittance(&w, &p, &light, 0.0));
 
     let p = point!(-2,2,-2);
-    let in_shadow = is_shadowed(&w, &p, &light);
-    assert_eq!(false, in_shadow);
+    assert_eq!(Color::WHITE, shadow_transmittance(&w, &p, &light, 0.0));
+}
+
+#[test]
+fn transparent_shadow_test() {
+    let mut world = World::new();
+    let light = Light::point_light(point!(0,10,0), rgb!(1,1,1));
+    let mut glass = Shape::sphere();
+    glass.transform = Matrix4x4::translation(0.0, 5.0, 0.0);
+    glass.material.color = rgb!(1,0.5,1);
+    glass.material.transparency = 0.5;
+    world.objects.push(glass);
+    let p = point!(0,0,0);
+
+    // the light passes through two surfaces of the sphere
+    assert_eq!(rgb!(0.25,0.0625,0.25), shadow_transmittance(&world, &p, &light, 0.0));
+
+    // objects that do not cast shadows are ignored
+    world.objects[0].material.casts_shadow = false;
+    assert_eq!(Color::WHITE, shadow_transmittance(&world, &p, &light, 0.0));
+
+    // an opaque object blocks the light no matter what else is in the way
+    world.objects[0].material.casts_shadow = true;
+    let mut blocker = Shape::sphere();
+    blocker.transform = Matrix4x4::translation(0.0, 2.0, 0.0);
+    world.objects.push(blocker);
+    assert_eq!(Color::BLACK, shadow_transmittance(&world, &p, &light, 0.0));
+}
+
+#[test]
+fn ambient_occlusion_test() {
+    // a floor lit only by its ambient term under a low ceiling
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(0,10,0), rgb!(1,1,1)));
+    let mut floor = Shape::plane();
+    floor.material.ambient = 1.0;
+    floor.material.diffuse = 0.0;
+    floor.material.specular = 0.0;
+    world.objects.push(floor);
+    let mut ceiling = Shape::plane();
+    ceiling.transform = Matrix4x4::translation(0.0, 0.5, 0.0);
+    world.objects.push(ceiling);
+
+    let ray = Ray::new(point!(0,0.25,0), vector!(0,-1,0));
+    assert_eq!(world.color_at(&ray), Color::WHITE);
+
+    // the ceiling is within reach of every occlusion ray
+    world.ambient_occlusion = Some(AmbientOcclusion::new(16, 1000.0));
+    assert_eq!(world.color_at(&ray), Color::BLACK);
+
+    // only grazing rays escape, the same point always gets the same answer
+    world.ambient_occlusion = Some(AmbientOcclusion::new(16, 1.0));
+    let color = world.color_at(&ray);
+    assert!(color.red > 0.0 && color.red < 1.0);
+    assert_eq!(color, world.color_at(&ray));
+}
+
+#[test]
+fn fog_test() {
+    let mut world = World::new();
+    world.background = Background::Solid(Color::WHITE);
+    world.fog = Some(Fog::new(rgb!(0.5,0.5,0.5), 0.1));
+
+    // rays that hit nothing see only fog
+    let ray = Ray::new(point!(0,0,0), vector!(0,0,1));
+    assert_eq!(world.color_at(&ray), rgb!(0.5,0.5,0.5));
+
+    // unless there is no fog to see
+    world.fog = Some(Fog::new(rgb!(0.5,0.5,0.5), 0.0));
+    assert_eq!(world.color_at(&ray), Color::WHITE);
+    world.fog = Some(Fog::new(rgb!(0.5,0.5,0.5), 0.1));
+
+    // a black floor 10 units away shows through partially
+    let mut floor = Shape::plane();
+    floor.material.color = Color::BLACK;
+    world.objects.push(floor);
+    let ray = Ray::new(point!(0,10,0), vector!(0,-1,0));
+    let fogged = 0.5 * (1.0 - (-1.0 as Float).exp());
+    assert_eq!(world.color_at(&ray), rgb!(fogged,fogged,fogged));
+
+    // and the fog dims light on its way too
+    let light = Light::point_light(point!(0,11,0), rgb!(1,1,1));
+    let transmittance = (-1.0 as Float).exp();
+    assert_eq!(shadow_transmittance(&world, &point!(0,1,0), &light, 0.0), rgb!(transmittance,transmittance,transmittance));
+}
+
+#[test]
+fn volume_test() {
+    use crate::media::Volume;
+
+    // an unlit, absorbing unit sphere in front of a white background
+    let mut world = World::new();
+    world.background = Background::Solid(Color::WHITE);
+    let mut smoke = Shape::sphere();
+    smoke.material.volume = Some(Volume::new(0.5, Color::WHITE));
+    world.objects.push(smoke);
+
+    // straight through the middle crosses 2 units
+    let through = (-1.0 as Float).exp();
+    let ray = Ray::new(point!(0,0,-5), vector!(0,0,1));
+    assert_eq!(world.color_at(&ray), rgb!(through,through,through));
+
+    // starting in the middle crosses 1
+    let half = (-0.5 as Float).exp();
+    let ray = Ray::new(point!(0,0,0), vector!(0,0,1));
+    assert_eq!(world.color_at(&ray), rgb!(half,half,half));
+
+    // missing it entirely
+    let ray = Ray::new(point!(0,2,-5), vector!(0,0,1));
+    assert_eq!(world.color_at(&ray), Color::WHITE);
+
+    // lit from above it scatters light towards the eye
+    world.background = Background::Solid(Color::BLACK);
+    world.lights.push(Light::point_light(point!(0,10,0), rgb!(1,1,1)));
+    let ray = Ray::new(point!(0,0,-5), vector!(0,0,1));
+    let color = world.color_at(&ray);
+    assert!(color.red > 0.0 && color.red < 1.0);
+
+    // and shadows what is beneath it
+    let light = world.lights.first().unwrap();
+    assert_eq!(shadow_transmittance(&world, &point!(0,-5,0), light, 0.0), rgb!(through,through,through));
+    assert_eq!(shadow_transmittance(&world, &point!(0,0,0), light, 0.0), rgb!(half,half,half));
+}
+
+#[test]
+fn large_volume_test() {
+    use crate::media::Volume;
+
+    // far enough out that a small step past a boundary rounds away
+    let mut world = World::new();
+    world.background = Background::Solid(Color::WHITE);
+    let mut smoke = Shape::sphere();
+    smoke.transform = Matrix4x4::scaling(5000.0, 5000.0, 5000.0);
+    smoke.material.volume = Some(Volume::new(0.0001, Color::BLACK));
+    world.objects.push(smoke);
+
+    let through = (-1.0 as Float).exp();
+    let ray = Ray::new(point!(0,0,-20000), vector!(0,0,1));
+    assert_eq!(world.color_at(&ray), rgb!(through,through,through));
+
+    // with a surface inside it only the part in front counts
+    let mut wall = Shape::plane();
+    wall.transform = Matrix4x4::rotation_x(math::consts::PI / 2.0);
+    wall.material.ambient = 1.0;
+    wall.material.diffuse = 0.0;
+    wall.material.specular = 0.0;
+    world.objects.push(wall);
+    world.lights.push(Light::point_light(point!(0,0,-20000), rgb!(1,1,1)));
+    let half = (-0.5 as Float).exp();
+    assert_eq!(world.color_at(&ray), rgb!(half,half,half));
 }
@@ -0,0 +1,61 @@
+/*
+    the library as another crate sees it, only the public api
+    and the exported macros
+*/
+
+use ray_tracer_challenge::math::consts;
+use ray_tracer_challenge::render;
+use ray_tracer_challenge::render::RenderSettings;
+use ray_tracer_challenge::Camera;
+use ray_tracer_challenge::Canvas;
+use ray_tracer_challenge::Color;
+use ray_tracer_challenge::Light;
+use ray_tracer_challenge::Matrix4x4;
+use ray_tracer_challenge::Quaternion;
+use ray_tracer_challenge::Ray;
+use ray_tracer_challenge::Shape;
+use ray_tracer_challenge::World;
+use ray_tracer_challenge::point;
+use ray_tracer_challenge::vector;
+use ray_tracer_challenge::rgb;
+
+#[test]
+fn render_through_public_api_test() {
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-10,10,-10), rgb!(1,1,1)));
+    let mut sphere = Shape::sphere();
+    sphere.material.color = rgb!(1,0.2,0.2);
+    world.objects.push(sphere);
+
+    let mut camera = Camera::new(11, 11, consts::FRAC_PI_2);
+    camera.transform = Matrix4x4::view_transform(&point!(0,0,-5), &point!(0,0,0), &vector!(0,1,0));
+    let mut canvas = Canvas::new(11, 11);
+    render::render(&camera, &world, &RenderSettings::default(), &mut canvas);
+
+    let center = canvas.get_pixel(5, 5);
+    assert!(center.red > center.green);
+    assert_eq!(canvas.get_pixel(0, 0), Color::BLACK);
+}
+
+#[test]
+fn rays_and_rotations_through_public_api_test() {
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-10,10,-10), rgb!(1,1,1)));
+    world.objects.push(Shape::sphere());
+
+    // a camera's ray and one built by hand see the same thing
+    let mut camera = Camera::new(11, 11, consts::FRAC_PI_2);
+    camera.transform = Matrix4x4::view_transform(&point!(0,0,-5), &point!(0,0,0), &vector!(0,1,0));
+    let ray: Ray = camera.ray_for_pixel(5, 5);
+    let by_hand = Ray::new(point!(0,0,-5), vector!(0,0,1));
+    assert_eq!(world.color_at(&ray), world.color_at(&by_hand));
+    assert_eq!(world.color_at(&camera.ray_for_pixel_offset(5, 5, 0.5, 0.5)), world.color_at(&ray));
+
+    // rotations go between matrices and quaternions
+    let quarter = Quaternion::from_axis_angle(&vector!(0,1,0), consts::FRAC_PI_2);
+    let rotation = Matrix4x4::rotation_quaternion(&quarter);
+    assert_eq!(rotation, Matrix4x4::rotation_y(consts::FRAC_PI_2));
+    assert_eq!(Matrix4x4::rotation_quaternion(&rotation.to_quaternion()), rotation);
+    let decomposed = Matrix4x4::translation(1.0, 2.0, 3.0).multiply(&rotation).decompose();
+    assert_eq!(Matrix4x4::rotation_quaternion(&decomposed.rotation), rotation);
+}
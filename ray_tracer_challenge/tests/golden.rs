@@ -0,0 +1,215 @@
+/*
+    golden image regression tests
+
+    each reference scene is rendered small and compared with the
+    image checked in under golden/, a failure leaves the render and
+    the difference image in target/golden/ to look at
+
+    after an intended change to how things look, regenerate the
+    golden images with
+
+        UPDATE_GOLDEN=1 cargo test golden
+
+    and check the new images in along with the change
+*/
+
+use ray_tracer_challenge::background::Background;
+use ray_tracer_challenge::Camera;
+use ray_tracer_challenge::canvas;
+use ray_tracer_challenge::Canvas;
+use ray_tracer_challenge::Color;
+use ray_tracer_challenge::Light;
+use ray_tracer_challenge::Material;
+use ray_tracer_challenge::material::NormalPerturbation;
+use ray_tracer_challenge::Pattern;
+use ray_tracer_challenge::math::consts;
+use ray_tracer_challenge::math::Float;
+use ray_tracer_challenge::matrix;
+use ray_tracer_challenge::Matrix4x4;
+use ray_tracer_challenge::media::Fog;
+use ray_tracer_challenge::media::Volume;
+use ray_tracer_challenge::render;
+use ray_tracer_challenge::render::RenderSettings;
+use ray_tracer_challenge::Shape;
+use ray_tracer_challenge::World;
+use ray_tracer_challenge::point;
+use ray_tracer_challenge::vector;
+use ray_tracer_challenge::rgb;
+use std::path::PathBuf;
+
+const WIDTH : u32 = 80;
+const HEIGHT : u32 = 60;
+
+/*
+    a few 8 bit levels of rounding anywhere, edge pixels can flip
+    between f32 and f64 so a handful may be further out, but no more
+*/
+const MAX_RMSE : Float = 0.01;
+const MAX_ERROR : Float = 4.0 / 255.0;
+const MAX_OUTLIERS : usize = 4;
+
+fn camera(from: ray_tracer_challenge::Point, to: ray_tracer_challenge::Point) -> Camera {
+    let mut camera = Camera::new(WIDTH, HEIGHT, consts::PI / 3.0);
+    camera.transform = Matrix4x4::view_transform(&from, &to, &vector!(0,1,0));
+    camera
+}
+
+fn floor() -> Shape {
+    let mut floor = Shape::plane();
+    floor.material.pattern = Pattern::planar_checkered(rgb!(0.9,0.9,0.9), rgb!(0.3,0.3,0.3), matrix::MATRIX_4X4_IDENTITY);
+    floor.material.specular = 0.0;
+    floor
+}
+
+fn render(camera: &Camera, world: &World, settings: &RenderSettings) -> Canvas {
+    let mut canvas = Canvas::new(camera.hsize, camera.vsize);
+    render::render(camera, world, settings, &mut canvas);
+    canvas
+}
+
+fn directory(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(name)
+}
+
+fn check(name: &str, rendered: &Canvas) {
+    // compare what an 8 bit image can hold
+    let ppm = canvas::to_ppm(rendered);
+    let rendered = canvas::from_ppm(ppm.as_bytes()).unwrap();
+    let golden_path = directory("golden").join(format!("{}.ppm", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(directory("golden")).unwrap();
+        std::fs::write(&golden_path, ppm).unwrap();
+        return;
+    }
+
+    let data = std::fs::read(&golden_path)
+        .unwrap_or_else(|_| panic!("no golden image at {}, see golden.rs", golden_path.display()));
+    let golden = canvas::from_ppm(&data).unwrap();
+    let difference = canvas::diff(&rendered, &golden).unwrap();
+    let outliers = outliers(&difference.image);
+    if difference.rmse > MAX_RMSE || outliers > MAX_OUTLIERS {
+        let out = directory("target").join("golden");
+        std::fs::create_dir_all(&out).unwrap();
+        std::fs::write(out.join(format!("{}.ppm", name)), ppm).unwrap();
+        std::fs::write(out.join(format!("{}_diff.ppm", name)), canvas::to_ppm(&difference.image)).unwrap();
+        panic!(
+            "{} differs from its golden image, rmse {} psnr {} dB, {} pixels off by more than {}, max error {} at {:?}, see {}",
+            name,
+            difference.rmse,
+            difference.psnr,
+            outliers,
+            MAX_ERROR,
+            difference.max_error,
+            difference.max_error_at,
+            out.display());
+    }
+}
+
+/*
+    pixels off by more than MAX_ERROR in any channel, both images
+    hold whole 8 bit levels so half a level more keeps float rounding
+    of the difference from counting
+*/
+fn outliers(difference: &Canvas) -> usize {
+    let mut count = 0;
+    for y in 0..difference.height() {
+        for x in 0..difference.width() {
+            let error = difference.get_pixel(x, y);
+            if error.red.max(error.green).max(error.blue) > MAX_ERROR + 0.5 / 255.0 {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn golden_phong_test() {
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-10,10,-10), rgb!(1,1,1)));
+    world.objects.push(floor());
+
+    let mut striped = Shape::sphere();
+    striped.transform = Matrix4x4::translation(-0.5, 1.0, 0.5);
+    let pattern_transform = Matrix4x4::scaling(0.2,0.2,0.2).multiply(&Matrix4x4::rotation_z(consts::PI / 4.0));
+    striped.material.pattern = Pattern::stripe(rgb!(0.1,0.1,1), rgb!(1,1,1), pattern_transform);
+    world.objects.push(striped);
+
+    let mut small = Shape::sphere();
+    small.transform = Matrix4x4::translation(1.5, 0.5, -0.5).multiply(&Matrix4x4::scaling(0.5,0.5,0.5));
+    small.material.pattern = Pattern::ring(rgb!(0.5,1,0.1), rgb!(1,0.5,0.1), Matrix4x4::scaling(0.2,0.2,0.2));
+    world.objects.push(small);
+
+    let camera = camera(point!(0,1.5,-5), point!(0,1,0));
+    check("phong", &render(&camera, &world, &RenderSettings::new()));
+}
+
+#[test]
+fn golden_materials_test() {
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-5,10,-10), rgb!(1,1,1)));
+    world.background = Background::Gradient { bottom: rgb!(1,1,1), top: rgb!(0.4,0.6,1) };
+    world.objects.push(floor());
+
+    let mut metal = Shape::sphere();
+    metal.transform = Matrix4x4::translation(-1.5, 1.0, 0.0);
+    metal.material = Material::microfacet(rgb!(1,0.8,0.4), 0.3, 1.0);
+    world.objects.push(metal);
+
+    let mut glass = Shape::sphere();
+    glass.transform = Matrix4x4::translation(0.6, 1.0, 0.5);
+    glass.material.color = rgb!(0.6,0.8,1);
+    glass.material.transparency = 0.7;
+    world.objects.push(glass);
+
+    let mut bumpy = Shape::sphere();
+    bumpy.transform = Matrix4x4::translation(1.8, 0.5, -1.2).multiply(&Matrix4x4::scaling(0.5,0.5,0.5));
+    bumpy.material.color = rgb!(1,0.4,0.4);
+    bumpy.material.normal_perturbation = Some(NormalPerturbation::Noise { scale: 4.0, amount: 0.5, octaves: 2 });
+    world.objects.push(bumpy);
+
+    let camera = camera(point!(0,2,-6), point!(0,0.8,0));
+    check("materials", &render(&camera, &world, &RenderSettings::new()));
+}
+
+#[test]
+fn golden_atmosphere_test() {
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-3,6,-4), rgb!(1,1,1)));
+    world.fog = Some(Fog::new(rgb!(0.5,0.55,0.6), 0.05));
+    world.objects.push(floor());
+
+    let mut smoke = Shape::sphere();
+    smoke.transform = Matrix4x4::translation(0.0, 1.2, 0.0).multiply(&Matrix4x4::scaling(1.2,1.2,1.2));
+    smoke.material.volume = Some(Volume::new(0.8, rgb!(0.9,0.9,0.9)));
+    world.objects.push(smoke);
+
+    let mut post = Shape::sphere();
+    post.transform = Matrix4x4::translation(0.0, 0.4, 2.5).multiply(&Matrix4x4::scaling(0.4,0.4,0.4));
+    post.material.color = rgb!(0.8,0.2,0.2);
+    world.objects.push(post);
+
+    let camera = camera(point!(0,1.5,-6), point!(0,1,0));
+    check("atmosphere", &render(&camera, &world, &RenderSettings::new()));
+}
+
+#[test]
+fn golden_motion_blur_test() {
+    let mut world = World::new();
+    world.lights.push(Light::point_light(point!(-10,10,-10), rgb!(1,1,1)));
+    world.objects.push(floor());
+
+    let mut ball = Shape::sphere();
+    ball.transform = Matrix4x4::translation(-1.0, 1.0, 0.0);
+    ball.set_end_transform(Some(Matrix4x4::translation(1.0, 1.0, 0.0)));
+    ball.material.color = Color::WHITE;
+    world.objects.push(ball);
+
+    let mut camera = camera(point!(0,1.5,-5), point!(0,1,0));
+    camera.shutter_close = 1.0;
+    let mut settings = RenderSettings::new();
+    settings.samples_per_pixel = 16;
+    settings.seed = 7;
+    check("motion_blur", &render(&camera, &world, &settings));
+}